@@ -0,0 +1,157 @@
+// Compares the packed DynamicBlocks with the old storage,
+// where every block was an entity with BreakingState and BlockPosition
+//
+// cargo bench --bench dynamic_blocks
+
+#[allow(dead_code)]
+#[path = "../src/dynamic_blocks.rs"]
+mod dynamic_blocks;
+
+use bevy_ecs::prelude::*;
+use dynamic_blocks::DynamicBlocks;
+use std::{collections::HashMap, hint::black_box, time::Instant};
+use valence::{math::DVec3, BlockPos};
+
+const BLOCK_HP: u16 = 100;
+// Same size as the default arena: three 200x200 floors
+const FLOORS: [i32; 3] = [60, 70, 80];
+const HALF_SIZE: i32 = 100;
+const PLAYERS: usize = 500;
+const TICKS: usize = 200;
+
+#[derive(Component)]
+struct BreakingState {
+    hp: i32,
+}
+
+#[derive(Component)]
+struct BlockPosition {
+    pos: BlockPos,
+}
+
+fn arena_blocks() -> impl Iterator<Item = BlockPos> {
+    FLOORS.into_iter().flat_map(|y| {
+        (-HALF_SIZE..HALF_SIZE)
+            .flat_map(move |x| (-HALF_SIZE..HALF_SIZE).map(move |z| BlockPos::new(x, y, z)))
+    })
+}
+
+// Deterministic spread of players over the floors
+fn players() -> Vec<DVec3> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move |range: i32| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % range as u64) as i32
+    };
+    (0..PLAYERS)
+        .map(|_| {
+            let x = next(HALF_SIZE * 2) - HALF_SIZE;
+            let z = next(HALF_SIZE * 2) - HALF_SIZE;
+            let y = FLOORS[next(FLOORS.len() as i32) as usize] + 1;
+            DVec3::new(x as f64 + 0.3, y as f64, z as f64 + 0.7)
+        })
+        .collect()
+}
+
+// Same candidates break_blocks_under_player checks
+fn blocks_under(pos: DVec3) -> impl Iterator<Item = BlockPos> {
+    [-0.5, 0.0, 0.5]
+        .into_iter()
+        .flat_map(|x| [-0.5, 0.0, 0.5].map(|z| (x, z)))
+        .map(move |(x, z)| (pos + DVec3::new(x, -0.5, z)).into())
+}
+
+fn report(name: &str, setup: std::time::Duration, ticks: std::time::Duration) {
+    println!(
+        "{name:>16}: setup {:>8.2?}, {:>8.2?} per tick",
+        setup,
+        ticks / TICKS as u32
+    );
+}
+
+fn entity_per_block(players: &[DVec3]) {
+    let start = Instant::now();
+    let mut world = World::new();
+    let mut data: HashMap<BlockPos, Entity> = HashMap::new();
+    for pos in arena_blocks() {
+        let e = world
+            .spawn((
+                BreakingState {
+                    hp: BLOCK_HP as i32,
+                },
+                BlockPosition { pos },
+            ))
+            .id();
+        data.insert(pos, e);
+    }
+    let mut changed =
+        world.query_filtered::<(Entity, &BlockPosition, &BreakingState), Changed<BreakingState>>();
+    world.clear_trackers();
+    let setup = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        for &player in players {
+            for pos in blocks_under(player) {
+                let Some(&e) = data.get(&pos) else {
+                    continue;
+                };
+                if let Some(mut state) = world.get_mut::<BreakingState>(e) {
+                    state.hp -= 1;
+                }
+                break;
+            }
+        }
+        let mut destroyed = vec![];
+        for (e, block, state) in changed.iter(&world) {
+            black_box((e.index(), block.pos, state.hp));
+            if state.hp <= 0 {
+                destroyed.push((e, block.pos));
+            }
+        }
+        for (e, pos) in destroyed {
+            data.remove(&pos);
+            world.despawn(e);
+        }
+        world.clear_trackers();
+    }
+    report("entity per block", setup, start.elapsed());
+}
+
+fn packed(players: &[DVec3]) {
+    let start = Instant::now();
+    let mut dynamic = DynamicBlocks::default();
+    for pos in arena_blocks() {
+        dynamic.insert(pos, BLOCK_HP);
+    }
+    let setup = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        for &player in players {
+            for pos in blocks_under(player) {
+                if dynamic.contains(pos) {
+                    dynamic.damage(pos, 1);
+                    break;
+                }
+            }
+        }
+        let dirty: Vec<_> = dynamic.drain_dirty().collect();
+        for (pos, hp) in dirty {
+            let stage = dynamic_blocks::destroy_stage(hp, BLOCK_HP);
+            black_box((dynamic.breaker_id(pos), stage));
+            if stage >= 10 {
+                dynamic.release_breaker(pos);
+            }
+        }
+    }
+    report("packed", setup, start.elapsed());
+}
+
+fn main() {
+    let players = players();
+    entity_per_block(&players);
+    packed(&players);
+}
@@ -1,6 +1,8 @@
+use serde::Deserialize;
 use valence::{BlockPos, ChunkPos};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(from = "[[i32; 3]; 2]")]
 pub struct Area {
     min: BlockPos,
     max: BlockPos,
@@ -14,6 +16,12 @@ pub fn block_pos_max(a: BlockPos, b: BlockPos) -> BlockPos {
     BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
 }
 
+impl From<[[i32; 3]; 2]> for Area {
+    fn from([a, b]: [[i32; 3]; 2]) -> Self {
+        Self::new(a, b)
+    }
+}
+
 impl Area {
     pub fn new(a: impl Into<BlockPos>, b: impl Into<BlockPos>) -> Self {
         let a = a.into();
@@ -50,6 +58,20 @@ impl Area {
             && pos.z <= self.max.z
     }
 
+    // Index of pos in iter_block_pos order
+    pub fn index_of(&self, pos: impl Into<BlockPos>) -> Option<usize> {
+        let pos = pos.into();
+        if !self.contains(pos) {
+            return None;
+        }
+        let size_y = (self.max.y - self.min.y + 1) as usize;
+        let size_z = (self.max.z - self.min.z + 1) as usize;
+        let x = (pos.x - self.min.x) as usize;
+        let y = (pos.y - self.min.y) as usize;
+        let z = (pos.z - self.min.z) as usize;
+        Some((x * size_y + y) * size_z + z)
+    }
+
     pub fn iter_block_pos(&self) -> impl Iterator<Item = BlockPos> {
         // copy so iterator won't capture self
         let min = self.min;
@@ -0,0 +1,214 @@
+use crate::{
+    area::Area,
+    game::Match,
+    hud::{BossBarMessage, TitleMessage},
+    level::{ArenaLayer, ArenaPlayer, WOOL},
+    teams,
+};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::{HashMap, HashSet};
+use valence::{boss_bar::BossBarColor, prelude::*};
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    // Players look for the colour until timer reaches zero
+    Dancing {
+        target: BlockState,
+        timer: i64,
+        total: i64,
+    },
+    // Everything but the target colour is gone
+    Removed {
+        timer: i64,
+    },
+}
+
+// Block Party state of an arena, rounds go on while the match is running
+#[derive(Component)]
+pub struct BlockParty {
+    floor: Area,
+    round: u32,
+    stage: Option<Stage>,
+}
+
+impl BlockParty {
+    pub fn new(floor: Area) -> Self {
+        Self {
+            floor,
+            round: 0,
+            stage: None,
+        }
+    }
+}
+
+fn random_point(rng: &mut impl Rng, floor: &Area) -> [i32; 2] {
+    let min = floor.min();
+    let max = floor.max();
+    [rng.gen_range(min.x..=max.x), rng.gen_range(min.z..=max.z)]
+}
+
+enum Pattern {
+    Stripes { width: i32, along_x: bool },
+    Diagonal { width: i32 },
+    Rings { width: i32, center: [i32; 2] },
+    // Square tiles of random colours
+    Tiles { size: i32 },
+    // Every block takes the colour of the nearest seed
+    Blobs { seeds: Vec<[i32; 2]> },
+}
+
+impl Pattern {
+    fn random(rng: &mut impl Rng, floor: &Area, colors: usize) -> Self {
+        match rng.gen_range(0..5) {
+            0 => Pattern::Stripes {
+                width: rng.gen_range(1..=3),
+                along_x: rng.gen(),
+            },
+            1 => Pattern::Diagonal {
+                width: rng.gen_range(1..=3),
+            },
+            2 => Pattern::Rings {
+                width: rng.gen_range(1..=2),
+                center: random_point(rng, floor),
+            },
+            3 => Pattern::Tiles {
+                size: rng.gen_range(2..=4),
+            },
+            _ => Pattern::Blobs {
+                seeds: (0..colors * 2).map(|_| random_point(rng, floor)).collect(),
+            },
+        }
+    }
+
+    // Index of the colour at the block
+    fn color(
+        &self,
+        rng: &mut impl Rng,
+        tiles: &mut HashMap<[i32; 2], usize>,
+        [x, z]: [i32; 2],
+        colors: usize,
+    ) -> usize {
+        let n = colors as i32;
+        match self {
+            Pattern::Stripes { width, along_x } => {
+                let coord = if *along_x { x } else { z };
+                coord.div_euclid(*width).rem_euclid(n) as usize
+            }
+            Pattern::Diagonal { width } => (x + z).div_euclid(*width).rem_euclid(n) as usize,
+            Pattern::Rings { width, center } => {
+                let dx = (x - center[0]) as f64;
+                let dz = (z - center[1]) as f64;
+                let distance = (dx * dx + dz * dz).sqrt() as i32;
+                (distance / width).rem_euclid(n) as usize
+            }
+            Pattern::Tiles { size } => *tiles
+                .entry([x.div_euclid(*size), z.div_euclid(*size)])
+                .or_insert_with(|| rng.gen_range(0..colors)),
+            Pattern::Blobs { seeds } => seeds
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, seed)| (seed[0] - x).pow(2) + (seed[1] - z).pow(2))
+                .map_or(0, |(i, _)| i % colors),
+        }
+    }
+}
+
+// Paints a new random pattern and returns the colours that made it onto the floor
+fn paint_floor(
+    rng: &mut impl Rng,
+    layer: &mut ChunkLayer,
+    floor: &Area,
+    colors: usize,
+) -> Vec<BlockState> {
+    let palette: Vec<BlockState> = WOOL.choose_multiple(rng, colors).copied().collect();
+    let pattern = Pattern::random(rng, floor, colors);
+    let mut tiles = HashMap::new();
+    let mut used = HashSet::new();
+    for pos in floor.iter_block_pos() {
+        let state = palette[pattern.color(rng, &mut tiles, [pos.x, pos.z], colors)];
+        layer.set_block(pos, state);
+        used.insert(state);
+    }
+    palette
+        .into_iter()
+        .filter(|state| used.contains(state))
+        .collect()
+}
+
+fn color_text(wool: BlockState) -> Text {
+    match teams::wool_def(wool) {
+        Some(def) => def.name.bold().color(def.color),
+        None => "?".bold(),
+    }
+}
+
+pub fn update_block_party(
+    mut arenas: Query<(Entity, &Match, &mut BlockParty, &mut ChunkLayer), With<ArenaLayer>>,
+    players: Query<(Entity, &EntityLayerId), With<ArenaPlayer>>,
+    mut commands: Commands,
+) {
+    let mut rng = rand::thread_rng();
+    for (arena, game, mut party, mut layer) in arenas.iter_mut() {
+        let Some(rules) = &game.rules.block_party else {
+            continue;
+        };
+        // the floor comes back with the rest of the arena
+        if !game.is_running() {
+            party.round = 0;
+            party.stage = None;
+            continue;
+        }
+        let party = party.as_mut();
+        party.stage = match party.stage {
+            Some(Stage::Removed { timer }) if timer > 0 => {
+                Some(Stage::Removed { timer: timer - 1 })
+            }
+            None | Some(Stage::Removed { .. }) => {
+                party.round += 1;
+                let colors = paint_floor(&mut rng, &mut layer, &party.floor, rules.colors);
+                let Some(&target) = colors.choose(&mut rng) else {
+                    continue;
+                };
+                let total = rules.round_ticks(party.round);
+                let mut title =
+                    TitleMessage::new(color_text(target), format!("Round {}", party.round));
+                title.stay_ticks = total as i32;
+                for (player, _) in players.iter().filter(|(_, layer)| layer.0 == arena) {
+                    commands.entity(player).insert(title.clone());
+                }
+                Some(Stage::Dancing {
+                    target,
+                    timer: total,
+                    total,
+                })
+            }
+            Some(Stage::Dancing {
+                target,
+                timer,
+                total,
+            }) if timer > 0 => {
+                commands.entity(arena).insert(BossBarMessage {
+                    title: "Stand on ".into_text() + color_text(target),
+                    progress: timer as f32 / total as f32,
+                    color: BossBarColor::White,
+                });
+                Some(Stage::Dancing {
+                    target,
+                    timer: timer - 1,
+                    total,
+                })
+            }
+            Some(Stage::Dancing { target, .. }) => {
+                for pos in party.floor.iter_block_pos() {
+                    if layer.block(pos).is_some_and(|block| block.state != target) {
+                        layer.set_block(pos, BlockState::AIR);
+                    }
+                }
+                commands.entity(arena).remove::<BossBarMessage>();
+                Some(Stage::Removed {
+                    timer: rules.removed_ticks(),
+                })
+            }
+        };
+    }
+}
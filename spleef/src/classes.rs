@@ -1,9 +1,19 @@
 use crate::{
-    area::Area,
-    level::{ArenaLayer, WOOL},
+    config::ConfigError,
+    damage::{BlockDamageEvent, DamageCause},
+    dynamic_blocks::DynamicBlocks,
+    game::{self, ArenaBounds, Match},
+    level::ArenaLayer,
+    raycast::{raycast, raycast_aabb, RaycastHit},
+    splegg::Splegg,
+    teams::{self, Team},
 };
 use bevy_ecs::query::WorldQuery;
-use std::collections::HashSet;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 use valence::{
     entity::{
         arrow::ArrowEntityBundle,
@@ -16,109 +26,252 @@ use valence::{
     interact_block::InteractBlockEvent,
     interact_item::InteractItemEvent,
     inventory::{player_inventory::PlayerInventory, HeldItem},
-    math::{IVec3, Vec3Swizzles},
+    math::{IVec3, Vec2, Vec3Swizzles},
     prelude::*,
     DEFAULT_TPS,
 };
 
-#[derive(Component)]
-pub struct ClassName(pub &'static str);
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct ItemName(pub ItemKind);
 
-pub trait GameClass: Default {
-    fn name() -> &'static str;
+impl TryFrom<String> for ItemName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(&name);
+        ItemKind::from_str(name)
+            .map(Self)
+            .ok_or_else(|| format!("unknown item {name:?}"))
+    }
 }
 
-#[derive(Component, Default)]
-pub struct WarriorClass;
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeName {
+    MovementSpeed,
+    MaxHealth,
+    KnockbackResistance,
+    AttackDamage,
+}
 
-impl GameClass for WarriorClass {
-    fn name() -> &'static str {
-        "Warrior"
+impl AttributeName {
+    const ALL: [AttributeName; 4] = [
+        AttributeName::MovementSpeed,
+        AttributeName::MaxHealth,
+        AttributeName::KnockbackResistance,
+        AttributeName::AttackDamage,
+    ];
+
+    pub fn attribute(self) -> EntityAttribute {
+        match self {
+            AttributeName::MovementSpeed => EntityAttribute::GenericMovementSpeed,
+            AttributeName::MaxHealth => EntityAttribute::GenericMaxHealth,
+            AttributeName::KnockbackResistance => EntityAttribute::GenericKnockbackResistance,
+            AttributeName::AttackDamage => EntityAttribute::GenericAttackDamage,
+        }
+    }
+
+    // Vanilla player values
+    pub fn default_value(self) -> f64 {
+        match self {
+            AttributeName::MovementSpeed => 0.1,
+            AttributeName::MaxHealth => 20.0,
+            AttributeName::KnockbackResistance => 0.0,
+            AttributeName::AttackDamage => 1.0,
+        }
     }
 }
 
-#[derive(Component, Default)]
-pub struct ArcherClass;
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KitGameMode {
+    Survival,
+    #[default]
+    Adventure,
+}
 
-impl GameClass for ArcherClass {
-    fn name() -> &'static str {
-        "Archer"
+impl From<KitGameMode> for GameMode {
+    fn from(mode: KitGameMode) -> Self {
+        match mode {
+            KitGameMode::Survival => GameMode::Survival,
+            KitGameMode::Adventure => GameMode::Adventure,
+        }
     }
 }
 
-#[derive(Component, Default)]
-pub struct MageClass;
+#[derive(Deserialize, Debug, Clone)]
+pub struct KitItem {
+    // Hotbar slot
+    pub slot: u16,
+    pub item: ItemName,
+    #[serde(default = "KitItem::default_count")]
+    pub count: i8,
+}
 
-impl GameClass for MageClass {
-    fn name() -> &'static str {
-        "Mage"
+impl KitItem {
+    fn default_count() -> i8 {
+        1
     }
 }
 
-#[derive(Component, Default)]
-pub struct RogueClass;
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Ability {
+    // Breaks destructible blocks instantly
+    Dig,
+    Arrow { speed: f32 },
+    // Breaks the hit block and its neighbours
+    Fireball { speed: f32 },
+    // Multiplies melee knockback
+    Knockback { multiplier: f32 },
+}
 
-impl GameClass for RogueClass {
-    fn name() -> &'static str {
-        "Rogue"
-    }
+#[derive(Deserialize, Debug, Clone)]
+pub struct AbilityDef {
+    // Ability is used only while holding this item
+    pub item: ItemName,
+    #[serde(default)]
+    pub cooldown_ticks: i32,
+    #[serde(flatten)]
+    pub ability: Ability,
 }
 
-fn clear_inventory(inv: &mut Inventory) {
-    for slot in 0..inv.slot_count() {
-        inv.set_slot(slot, ItemStack::EMPTY);
-    }
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClassDef {
+    // Used in triggers, signs and commands
+    pub id: String,
+    pub name: String,
+    // Shown above the class trigger in the lobby
+    pub display: String,
+    #[serde(default)]
+    pub game_mode: KitGameMode,
+    #[serde(default)]
+    pub kit: Vec<KitItem>,
+    #[serde(default)]
+    pub attributes: HashMap<AttributeName, f64>,
+    pub ability: Option<AbilityDef>,
 }
 
-pub fn init_warrior(
-    mut clients: Query<(&mut Inventory, &mut GameMode), (With<Client>, Added<WarriorClass>)>,
-) {
-    for (mut inv, mut game_mode) in clients.iter_mut() {
-        *game_mode = GameMode::Survival;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClassId(usize);
 
-        let inv = inv.as_mut();
-        clear_inventory(inv);
+#[derive(Deserialize)]
+struct ClassFile {
+    classes: Vec<ClassDef>,
+}
 
-        inv.set_slot(
-            PlayerInventory::hotbar_to_slot(0),
-            ItemStack::new(ItemKind::WoodenShovel, 1, None),
-        );
+#[derive(Resource)]
+pub struct ClassRegistry {
+    classes: Vec<ClassDef>,
+}
+
+impl ClassRegistry {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let data =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let file: ClassFile =
+            toml::from_str(&data).map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        let mut ids = HashSet::new();
+        for class in file.classes.iter() {
+            if !ids.insert(class.id.as_str()) {
+                return Err(ConfigError::Invalid(format!(
+                    "class {:?} is defined twice in {}",
+                    class.id,
+                    path.display()
+                )));
+            }
+            if class.kit.iter().any(|item| item.slot > 8) {
+                return Err(ConfigError::Invalid(format!(
+                    "class {:?} has a kit item outside of the hotbar",
+                    class.id
+                )));
+            }
+        }
+        Ok(Self {
+            classes: file.classes,
+        })
+    }
+
+    pub fn get(&self, id: ClassId) -> &ClassDef {
+        &self.classes[id.0]
+    }
+
+    pub fn find(&self, id: &str) -> Option<ClassId> {
+        self.classes.iter().position(|c| c.id == id).map(ClassId)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ClassId, &ClassDef)> {
+        self.classes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (ClassId(i), c))
+    }
+
+    // Ability of the player's class, if they're holding its item
+    pub fn held_ability(
+        &self,
+        class: &PlayerClass,
+        held: &HeldItem,
+        inv: &Inventory,
+    ) -> Option<&AbilityDef> {
+        let ability = self.get(class.0).ability.as_ref()?;
+        (inv.slot(held.slot()).item == ability.item.0).then_some(ability)
     }
 }
 
-pub fn init_archer(mut clients: Query<&mut Inventory, (With<Client>, Added<ArcherClass>)>) {
-    for mut inv in clients.iter_mut() {
-        let inv = inv.as_mut();
-        clear_inventory(inv);
-        inv.set_slot(
-            PlayerInventory::hotbar_to_slot(0),
-            ItemStack::new(ItemKind::Bow, 1, None),
-        );
+#[derive(Component)]
+pub struct PlayerClass(pub ClassId);
+
+#[derive(Component)]
+pub struct ClassName(pub String);
+
+// Everything a player gets when picking a class
+pub type ClassComponents = (PlayerClass, ClassName, CombatState, Cooldown);
+
+pub fn clear_inventory(inv: &mut Inventory) {
+    for slot in 0..inv.slot_count() {
+        inv.set_slot(slot, ItemStack::EMPTY);
     }
 }
 
-pub fn init_mage(mut clients: Query<&mut Inventory, (With<Client>, Added<MageClass>)>) {
-    for mut inv in clients.iter_mut() {
-        let inv = inv.as_mut();
-        clear_inventory(inv);
-        inv.set_slot(
-            PlayerInventory::hotbar_to_slot(0),
-            ItemStack::new(ItemKind::FireworkRocket, 1, None),
-        );
+pub fn reset_attributes(attr: &mut EntityAttributes) {
+    for name in AttributeName::ALL {
+        attr.set_base_value(name.attribute(), name.default_value());
     }
 }
 
-pub fn init_rogue(
-    mut clients: Query<(&mut Inventory, &mut EntityAttributes), (With<Client>, Added<RogueClass>)>,
+pub fn init_class(
+    mut clients: Query<
+        (
+            &PlayerClass,
+            &mut Inventory,
+            &mut GameMode,
+            &mut EntityAttributes,
+        ),
+        (With<Client>, Added<PlayerClass>),
+    >,
+    registry: Res<ClassRegistry>,
 ) {
-    for (mut inv, mut attr) in clients.iter_mut() {
-        attr.set_base_value(EntityAttribute::GenericMovementSpeed, 0.2);
+    for (class, mut inv, mut game_mode, mut attr) in clients.iter_mut() {
+        let class = registry.get(class.0);
+        *game_mode = class.game_mode.into();
+
         let inv = inv.as_mut();
         clear_inventory(inv);
-        inv.set_slot(
-            PlayerInventory::hotbar_to_slot(0),
-            ItemStack::new(ItemKind::WoodenSword, 1, None),
-        );
+        for item in class.kit.iter() {
+            inv.set_slot(
+                PlayerInventory::hotbar_to_slot(item.slot),
+                ItemStack::new(item.item.0, item.count, None),
+            );
+        }
+
+        let attr = attr.as_mut();
+        reset_attributes(attr);
+        for (name, value) in class.attributes.iter() {
+            attr.set_base_value(name.attribute(), *value);
+        }
     }
 }
 
@@ -134,35 +287,91 @@ pub fn update_cooldown(mut clients: Query<(Entity, &mut Cooldown)>, mut commands
     }
 }
 
-pub fn warrior_dig(
+pub fn dig_ability(
     mut clients: Query<
-        (&HeldItem, &Inventory),
-        (With<Client>, With<WarriorClass>, Without<Cooldown>),
+        (&HeldItem, &Inventory, &EntityLayerId, &PlayerClass),
+        (With<Client>, Without<Cooldown>),
     >,
+    registry: Res<ClassRegistry>,
     mut digging: EventReader<DiggingEvent>,
-    mut arena: Query<&mut ChunkLayer, With<ArenaLayer>>,
+    // digging doesn't break blocks in splegg
+    arenas: Query<(&DynamicBlocks, &Match), (With<ArenaLayer>, Without<Splegg>)>,
+    mut damage: EventWriter<BlockDamageEvent>,
     mut commands: Commands,
 ) {
-    let mut arena = arena.single_mut();
     let mut processed: HashSet<Entity> = Default::default();
     for event in digging.read() {
-        let Ok((held, inv)) = clients.get_mut(event.client) else {
+        let Ok((held, inv, layer, class)) = clients.get_mut(event.client) else {
+            continue;
+        };
+        let Some(ability) = registry.held_ability(class, held, inv) else {
             continue;
         };
-        let slot = inv.slot(held.slot());
-        if slot.item != ItemKind::WoodenShovel {
+        if ability.ability != Ability::Dig {
             continue;
         }
-        let Some(block) = arena.block(event.position) else {
+        let Ok((arena, game)) = arenas.get(layer.0) else {
             continue;
         };
-        if WOOL.contains(&block.state) {
+        if !game.is_running() {
+            continue;
+        }
+        if arena.contains(event.position) {
             if !processed.insert(event.client) {
                 continue;
             }
-            arena.set_block(event.position, BlockState::AIR);
-            commands.entity(event.client).insert(Cooldown(1));
+            damage.send(BlockDamageEvent {
+                layer: layer.0,
+                pos: event.position,
+                amount: BlockDamageEvent::DESTROY,
+                source: event.client,
+                cause: DamageCause::Dig,
+            });
+            commands
+                .entity(event.client)
+                .insert(Cooldown(ability.cooldown_ticks));
+        }
+    }
+}
+
+// Player who launched the projectile
+#[derive(Component)]
+pub struct Shooter(pub Entity);
+
+pub const PLAYER_WIDTH: f64 = 0.6;
+pub const PLAYER_HEIGHT: f64 = 1.8;
+
+pub enum ProjectileHit {
+    Block(RaycastHit),
+    Player(Entity),
+}
+
+// First block or player the projectile will fly into during this tick
+// players are (entity, position) of everyone who can be hit
+pub fn projectile_hit(
+    layer: &ChunkLayer,
+    pos: &Position,
+    vel: &Velocity,
+    players: impl Iterator<Item = (Entity, DVec3)>,
+) -> Option<ProjectileHit> {
+    let vel = vel.0.as_dvec3();
+    let max_dist = vel.length() / DEFAULT_TPS.get() as f64;
+    let block = raycast(layer, pos.0, vel, max_dist);
+    let half_width = DVec3::new(PLAYER_WIDTH / 2.0, 0.0, PLAYER_WIDTH / 2.0);
+    let player = players
+        .filter_map(|(player, feet)| {
+            let min = feet - half_width;
+            let max = feet + half_width + DVec3::new(0.0, PLAYER_HEIGHT, 0.0);
+            raycast_aabb(pos.0, vel, max_dist, min, max).map(|dist| (player, dist))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    match (block, player) {
+        (Some(block), Some((player, dist))) if dist < block.distance => {
+            Some(ProjectileHit::Player(player))
         }
+        (Some(block), _) => Some(ProjectileHit::Block(block)),
+        (None, Some((player, _))) => Some(ProjectileHit::Player(player)),
+        (None, None) => None,
     }
 }
 
@@ -180,63 +389,101 @@ pub fn arrow_movement(mut arrows: Query<(&mut Position, &mut Velocity), With<Arc
     }
 }
 
-pub fn arrow_oob(arrows: Query<(Entity, &Position), With<ArcherArrow>>, mut commands: Commands) {
-    let bounds = Area::new(BlockPos::new(-100, 0, -100), BlockPos::new(100, 500, 100));
-    for (e, pos) in arrows.iter() {
+pub fn arrow_oob(
+    arrows: Query<(Entity, &Position, &EntityLayerId), With<ArcherArrow>>,
+    arenas: Query<&ArenaBounds>,
+    mut commands: Commands,
+) {
+    for (e, pos, layer) in arrows.iter() {
         let block_pos: BlockPos = pos.0.into();
-        if !bounds.contains(block_pos) {
+        let in_bounds = arenas
+            .get(layer.0)
+            .is_ok_and(|bounds| bounds.projectiles.contains(block_pos));
+        if !in_bounds {
             commands.entity(e).insert(Despawned);
         }
     }
 }
 
 pub fn arrow_intersection(
-    arrows: Query<(Entity, &Position, &Velocity), With<ArcherArrow>>,
-    mut arena: Query<&mut ChunkLayer, With<ArenaLayer>>,
+    server: Res<Server>,
+    arrows: Query<(Entity, &Position, &Velocity, &EntityLayerId, &Shooter), With<ArcherArrow>>,
+    mut players: Query<CombatQuery, Without<ArcherArrow>>,
+    arenas: Query<(&ChunkLayer, &Match), With<ArenaLayer>>,
+    mut damage: EventWriter<BlockDamageEvent>,
     mut commands: Commands,
 ) {
-    let mut arena = arena.single_mut();
-    for (e, pos, vel) in arrows.iter() {
-        let mut last_block_pos: Option<BlockPos> = None;
-        // too lazy to raycast and this works fine-ish
-        for i in 0..10 {
-            let block_pos: BlockPos = (pos.0 + (vel.0 * i as f32 / 200.0).as_dvec3()).into();
-            if let Some(last_block_pos) = last_block_pos {
-                if block_pos == last_block_pos {
+    for (e, pos, vel, layer, shooter) in arrows.iter() {
+        let Ok((arena, game)) = arenas.get(layer.0) else {
+            continue;
+        };
+        let knockback = &game.rules.knockback;
+        let shooter_team = players.get(shooter.0).ok().and_then(|p| p.team);
+        let targets = players
+            .iter()
+            .filter(|p| p.layer.0 == layer.0 && p.entity != shooter.0)
+            .filter(|p| !teams::allies(p.team, shooter_team))
+            .map(|p| (p.entity, p.pos.0));
+        match projectile_hit(arena, pos, vel, targets) {
+            Some(ProjectileHit::Block(hit)) => {
+                damage.send(BlockDamageEvent {
+                    layer: layer.0,
+                    pos: hit.block,
+                    amount: BlockDamageEvent::DESTROY,
+                    source: shooter.0,
+                    cause: DamageCause::Arrow,
+                });
+            }
+            Some(ProjectileHit::Player(player)) => {
+                let landed = players.get_mut(player).is_ok_and(|mut victim| {
+                    victim.knockback(
+                        shooter.0,
+                        server.current_tick(),
+                        vel.0.xz(),
+                        knockback.horizontal * knockback.arrow_multiplier,
+                        knockback.vertical,
+                    )
+                });
+                // flies through players who were hit recently
+                if !landed {
                     continue;
                 }
             }
-            last_block_pos = Some(block_pos);
-            let Some(block) = arena.block(block_pos) else {
-                continue;
-            };
-            if block.state == BlockState::AIR {
-                continue;
-            }
-            if WOOL.contains(&block.state) {
-                arena.set_block(block_pos, BlockState::AIR);
-            }
-            commands.entity(e).insert(Despawned);
-            break;
+            None => continue,
         }
+        commands.entity(e).insert(Despawned);
     }
 }
 
-pub fn archer_shoot(
+pub fn arrow_ability(
     mut clients: Query<
-        (&HeldItem, &Inventory, &Position, &Look, &EntityLayerId),
-        (With<Client>, With<ArcherClass>, Without<Cooldown>),
+        (
+            &HeldItem,
+            &Inventory,
+            &Position,
+            &Look,
+            &EntityLayerId,
+            &PlayerClass,
+        ),
+        (With<Client>, Without<Cooldown>),
     >,
+    registry: Res<ClassRegistry>,
+    matches: Query<&Match>,
     mut interacts: EventReader<InteractItemEvent>,
     mut commands: Commands,
 ) {
     let mut processed: HashSet<Entity> = Default::default();
     for event in interacts.read() {
-        let Ok((held, inv, pos, look, entity_layer)) = clients.get_mut(event.client) else {
+        let Ok((held, inv, pos, look, entity_layer, class)) = clients.get_mut(event.client) else {
+            continue;
+        };
+        let Some(ability) = registry.held_ability(class, held, inv) else {
             continue;
         };
-        let slot = inv.slot(held.slot());
-        if slot.item != ItemKind::Bow {
+        let Ability::Arrow { speed } = ability.ability else {
+            continue;
+        };
+        if !game::is_running(&matches, entity_layer) {
             continue;
         }
         if !processed.insert(event.client) {
@@ -248,13 +495,16 @@ pub fn archer_shoot(
             ArrowEntityBundle {
                 entity_no_gravity: NoGravity(false),
                 position: Position(arrow_origin),
-                velocity: Velocity(look.vec() * 30.0),
+                velocity: Velocity(look.vec() * speed),
                 layer: *entity_layer,
                 ..Default::default()
             },
             ArcherArrow,
+            Shooter(event.client),
         ));
-        commands.entity(event.client).insert(Cooldown(5));
+        commands
+            .entity(event.client)
+            .insert(Cooldown(ability.cooldown_ticks));
     }
 }
 
@@ -269,82 +519,112 @@ pub fn fireball_movement(mut arrows: Query<(&mut Position, &Velocity), With<Mage
 }
 
 pub fn fireball_oob(
-    arrows: Query<(Entity, &Position), With<MageFireball>>,
+    arrows: Query<(Entity, &Position, &EntityLayerId), With<MageFireball>>,
+    arenas: Query<&ArenaBounds>,
     mut commands: Commands,
 ) {
-    let bounds = Area::new(BlockPos::new(-100, 0, -100), BlockPos::new(100, 100, 100));
-    for (e, pos) in arrows.iter() {
+    for (e, pos, layer) in arrows.iter() {
         let block_pos: BlockPos = pos.0.into();
-        if !bounds.contains(block_pos) {
+        let in_bounds = arenas
+            .get(layer.0)
+            .is_ok_and(|bounds| bounds.projectiles.contains(block_pos));
+        if !in_bounds {
             commands.entity(e).insert(Despawned);
         }
     }
 }
 
 pub fn fireball_intersection(
-    arrows: Query<(Entity, &Position, &Velocity), With<MageFireball>>,
-    mut arena: Query<&mut ChunkLayer, With<ArenaLayer>>,
+    server: Res<Server>,
+    fireballs: Query<(Entity, &Position, &Velocity, &EntityLayerId, &Shooter), With<MageFireball>>,
+    mut players: Query<CombatQuery, Without<MageFireball>>,
+    arenas: Query<(&ChunkLayer, &Match), With<ArenaLayer>>,
+    mut damage: EventWriter<BlockDamageEvent>,
     mut commands: Commands,
 ) {
-    let mut arena = arena.single_mut();
-    for (e, pos, vel) in arrows.iter() {
-        let mut last_block_pos: Option<BlockPos> = None;
-        // too lazy to raycast and this works fine-ish
-        for i in 0..10 {
-            let block_pos: BlockPos = (pos.0 + (vel.0 * i as f32 / 200.0).as_dvec3()).into();
-            if let Some(last_block_pos) = last_block_pos {
-                if block_pos == last_block_pos {
-                    continue;
+    for (e, pos, vel, layer, shooter) in fireballs.iter() {
+        let Ok((arena, game)) = arenas.get(layer.0) else {
+            continue;
+        };
+        let knockback = &game.rules.knockback;
+        let shooter_team = players.get(shooter.0).ok().and_then(|p| p.team);
+        let targets = players
+            .iter()
+            .filter(|p| p.layer.0 == layer.0 && p.entity != shooter.0)
+            .filter(|p| !teams::allies(p.team, shooter_team))
+            .map(|p| (p.entity, p.pos.0));
+        match projectile_hit(arena, pos, vel, targets) {
+            Some(ProjectileHit::Block(hit)) => {
+                for shift in [
+                    (-1, 0, 0),
+                    (0, -1, 0),
+                    (0, 0, -1),
+                    (1, 0, 0),
+                    (0, 1, 0),
+                    (0, 0, 1),
+                    (0, 0, 0),
+                ] {
+                    let shift: IVec3 = shift.into();
+                    damage.send(BlockDamageEvent {
+                        layer: layer.0,
+                        pos: hit.block + shift,
+                        amount: BlockDamageEvent::DESTROY,
+                        source: shooter.0,
+                        cause: DamageCause::Fireball,
+                    });
                 }
             }
-            last_block_pos = Some(block_pos);
-            let Some(block) = arena.block(block_pos) else {
-                continue;
-            };
-            if block.state == BlockState::AIR {
-                continue;
-            }
-            // collision
-            for shift in [
-                (-1, 0, 0),
-                (0, -1, 0),
-                (0, 0, -1),
-                (1, 0, 0),
-                (0, 1, 0),
-                (0, 0, 1),
-                (0, 0, 0),
-            ] {
-                let shift: IVec3 = shift.into();
-                let blasted_block_pos = block_pos + shift;
-                let Some(blasted_block) = arena.block(blasted_block_pos) else {
+            Some(ProjectileHit::Player(player)) => {
+                let landed = players.get_mut(player).is_ok_and(|mut victim| {
+                    victim.knockback(
+                        shooter.0,
+                        server.current_tick(),
+                        vel.0.xz(),
+                        knockback.horizontal * knockback.fireball_multiplier,
+                        knockback.vertical,
+                    )
+                });
+                // flies through players who were hit recently
+                if !landed {
                     continue;
-                };
-                if WOOL.contains(&blasted_block.state) {
-                    arena.set_block(blasted_block_pos, BlockState::AIR);
                 }
             }
-            commands.entity(e).insert(Despawned);
-            break;
+            None => continue,
         }
+        commands.entity(e).insert(Despawned);
     }
 }
 
-pub fn mage_shoot(
+pub fn fireball_ability(
     mut clients: Query<
-        (&HeldItem, &Inventory, &Position, &Look, &EntityLayerId),
-        (With<Client>, With<MageClass>, Without<Cooldown>),
+        (
+            &HeldItem,
+            &Inventory,
+            &Position,
+            &Look,
+            &EntityLayerId,
+            &PlayerClass,
+        ),
+        (With<Client>, Without<Cooldown>),
     >,
+    registry: Res<ClassRegistry>,
+    matches: Query<&Match>,
     mut item_interacts: EventReader<InteractItemEvent>,
     mut block_interacts: EventReader<InteractBlockEvent>,
     mut commands: Commands,
 ) {
     let mut processed: HashSet<Entity> = Default::default();
     let mut process = |client: Entity| {
-        let Ok((held, inv, pos, look, entity_layer)) = clients.get_mut(client) else {
+        let Ok((held, inv, pos, look, entity_layer, class)) = clients.get_mut(client) else {
+            return;
+        };
+        let Some(ability) = registry.held_ability(class, held, inv) else {
+            return;
+        };
+        let Ability::Fireball { speed } = ability.ability else {
             return;
         };
-        let slot = inv.slot(held.slot());
-        if slot.item != ItemKind::FireworkRocket {
+        if !game::is_running(&matches, entity_layer) {
             return;
         }
         if !processed.insert(client) {
@@ -358,14 +638,17 @@ pub fn mage_shoot(
                 thrown_item_item: Item(ItemStack::new(ItemKind::FireCharge, 1, None)),
                 entity_no_gravity: NoGravity(true),
                 position: Position(arrow_origin),
-                velocity: Velocity(look.vec() * 30.0),
+                velocity: Velocity(look.vec() * speed),
                 look: *look,
                 layer: *entity_layer,
                 ..Default::default()
             },
             MageFireball,
+            Shooter(client),
         ));
-        commands.entity(client).insert(Cooldown(15));
+        commands
+            .entity(client)
+            .insert(Cooldown(ability.cooldown_ticks));
     };
     for event in item_interacts.read() {
         process(event.client);
@@ -378,62 +661,115 @@ pub fn mage_shoot(
 #[derive(Default, Component)]
 pub struct CombatState {
     pub last_attacked_tick: i64,
+    pub last_attacker: Option<Entity>,
+}
+
+impl CombatState {
+    // Hits older than this don't count towards eliminations
+    const ATTRIBUTION_TICKS: i64 = DEFAULT_TPS.get() as i64 * 10;
+
+    // Player who should get credit if this one is eliminated now
+    pub fn recent_attacker(&self, current_tick: i64) -> Option<Entity> {
+        self.last_attacker
+            .filter(|_| current_tick - self.last_attacked_tick <= Self::ATTRIBUTION_TICKS)
+    }
 }
 
+// Players can't be knocked back again for half a second after a hit
+const INVULNERABILITY_TICKS: i64 = DEFAULT_TPS.get() as i64 / 2;
+
 #[derive(WorldQuery)]
 #[world_query(mutable)]
 pub struct CombatQuery {
+    entity: Entity,
     client: &'static mut Client,
     state: &'static mut CombatState,
     flags: &'static Flags,
     pos: &'static Position,
+    layer: &'static EntityLayerId,
     vel: &'static mut Velocity,
+    team: Option<&'static Team>,
+}
+
+impl CombatQueryItem<'_> {
+    // Pushes the player along dir, unless they were hit recently
+    // Returns whether the hit landed
+    fn knockback(
+        &mut self,
+        attacker: Entity,
+        current_tick: i64,
+        dir: Vec2,
+        xz: f32,
+        y: f32,
+    ) -> bool {
+        if current_tick - self.state.last_attacked_tick < INVULNERABILITY_TICKS {
+            return false;
+        }
+        self.state.last_attacked_tick = current_tick;
+        self.state.last_attacker = Some(attacker);
+        let dir = dir.normalize_or_zero();
+        let new_vel: Vec3 = [dir.x * xz, y, dir.y * xz].into();
+        self.client.set_velocity(new_vel);
+        self.vel.0 = new_vel;
+        true
+    }
 }
 
 pub fn combat(
     server: Res<Server>,
     mut clients: Query<CombatQuery>,
-    rogues: Query<(&HeldItem, &Inventory), With<RogueClass>>,
+    attackers: Query<(&HeldItem, &Inventory, &PlayerClass)>,
+    registry: Res<ClassRegistry>,
+    matches: Query<&Match>,
     mut interact_entity: EventReader<InteractEntityEvent>,
 ) {
     for event in interact_entity.read() {
         let Ok([attacker, mut victim]) = clients.get_many_mut([event.client, event.entity]) else {
             continue;
         };
-        let current_tick = server.current_tick();
-        if current_tick - victim.state.last_attacked_tick < DEFAULT_TPS.get() as i64 / 2 {
+        let Ok(game) = matches.get(attacker.layer.0) else {
+            continue;
+        };
+        if attacker.layer.0 != victim.layer.0 || !game.is_running() {
+            continue;
+        }
+        if teams::allies(attacker.team, victim.team) {
             continue;
         }
-        victim.state.last_attacked_tick = current_tick;
 
         let victim_pos = victim.pos.0.xz();
         let attacker_pos = attacker.pos.0.xz();
 
-        let dir = (victim_pos - attacker_pos).normalize().as_vec2();
-
-        let bonus_knockback = if let Ok((slot, inv)) = rogues.get(event.client) {
-            if inv.slot(slot.slot()).item == ItemKind::WoodenSword {
-                1.2
-            } else {
-                1.0
-            }
-        } else {
-            1.0
-        };
+        let dir = (victim_pos - attacker_pos).as_vec2();
+
+        let bonus_knockback = attackers
+            .get(event.client)
+            .ok()
+            .and_then(|(held, inv, class)| registry.held_ability(class, held, inv))
+            .and_then(|ability| match ability.ability {
+                Ability::Knockback { multiplier } => Some(multiplier),
+                _ => None,
+            })
+            .unwrap_or(1.0);
+        let knockback = &game.rules.knockback;
         let knockback_xz = if attacker.flags.sprinting() {
-            18.0
+            knockback.sprint_horizontal
         } else {
-            8.0
+            knockback.horizontal
         } * bonus_knockback;
         let knockback_y = if attacker.flags.sprinting() {
-            8.432
+            knockback.sprint_vertical
         } else {
-            6.432
+            knockback.vertical
         };
 
-        let new_vel: Vec3 = [dir.x * knockback_xz, knockback_y, dir.y * knockback_xz].into();
-        victim.client.set_velocity(new_vel);
-        victim.vel.0 = new_vel;
+        victim.knockback(
+            event.client,
+            server.current_tick(),
+            dir,
+            knockback_xz,
+            knockback_y,
+        );
 
         // victim.client.trigger_status(EntityStatus::PlayAttackSound);
         // victim.statuses.trigger(EntityStatus::PlayAttackSound);
@@ -0,0 +1,425 @@
+use crate::{
+    classes::{ClassRegistry, CombatState, PlayerClass},
+    game::{
+        self, Match, MatchPhase, PlayerEliminatedEvent, PlayerLeftMatchEvent, Spectator, Watcher,
+    },
+    level::{
+        arena_player_bundle, ArenaLayer, ArenaNumber, ArenaPlayer, ArenaRouter, ChunksLoading,
+        KeepPosition, LobbyPlayer, RestoreArena, ReturnToLobby,
+    },
+    map::SpawnPoints,
+};
+use valence::{
+    command::{handler::CommandResultEvent, CommandScopeRegistry},
+    command_macros::Command,
+    prelude::*,
+};
+
+// Every player gets this scope when they join
+pub const PLAYER_SCOPE: &str = "spleef.player";
+// Given to usernames listed in server.operators
+pub const OPERATOR_SCOPE: &str = "spleef.operator";
+
+const PLAYER_COMMANDS: [&str; 4] = ["leave", "class", "spectate", "stats"];
+const OPERATOR_COMMANDS: [&str; 4] = ["start", "stop", "reset", "arena"];
+
+pub fn link_scopes(scopes: &mut CommandScopeRegistry) {
+    for command in PLAYER_COMMANDS {
+        let scope = format!("spleef.command.{command}");
+        scopes.link(PLAYER_SCOPE, scope.as_str());
+        scopes.link(OPERATOR_SCOPE, scope.as_str());
+    }
+    for command in OPERATOR_COMMANDS {
+        scopes.link(OPERATOR_SCOPE, format!("spleef.command.{command}").as_str());
+    }
+}
+
+fn error(message: impl Into<String>) -> Text {
+    message.into().color(Color::RED)
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("leave")]
+#[scopes("spleef.command.leave")]
+pub struct LeaveCommand;
+
+// Leaving a running match counts as being eliminated from it
+pub fn handle_leave_command(
+    server: Res<Server>,
+    mut events: EventReader<CommandResultEvent<LeaveCommand>>,
+    mut clients: Query<(
+        &mut Client,
+        &Username,
+        &EntityLayerId,
+        Has<LobbyPlayer>,
+        Has<Watcher>,
+        Option<&CombatState>,
+        Option<&PlayerClass>,
+    )>,
+    mut arenas: Query<(&Match, &mut ChunkLayer), With<ArenaLayer>>,
+    mut eliminated: EventWriter<PlayerEliminatedEvent>,
+    mut left: EventWriter<PlayerLeftMatchEvent>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok((mut client, username, layer_id, in_lobby, watching, combat, class)) =
+            clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+        if in_lobby {
+            client.send_chat_message(error("You're already in the lobby"));
+            continue;
+        }
+        commands.entity(event.executor).insert(ReturnToLobby);
+
+        if watching {
+            continue;
+        }
+        let Ok((game, mut layer)) = arenas.get_mut(layer_id.0) else {
+            continue;
+        };
+        let Some((left_event, eliminated_event)) = game::leave_match(
+            game,
+            layer_id.0,
+            event.executor,
+            combat,
+            class,
+            server.current_tick(),
+        ) else {
+            continue;
+        };
+        left.send(left_event);
+        if let Some(eliminated_event) = eliminated_event {
+            layer.send_chat_message(username.0.clone().bold() + " left the match");
+            eliminated.send(eliminated_event);
+        }
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("class {class}")]
+#[scopes("spleef.command.class")]
+pub struct ClassCommand {
+    class: String,
+}
+
+// Same as walking into the class trigger
+pub fn handle_class_command(
+    mut events: EventReader<CommandResultEvent<ClassCommand>>,
+    mut clients: Query<(&mut Client, Has<LobbyPlayer>)>,
+    arenas: Query<(Entity, &Match), With<ArenaLayer>>,
+    players: Query<&EntityLayerId, With<ArenaPlayer>>,
+    registry: Res<ClassRegistry>,
+    mut commands: Commands,
+) {
+    let mut router = ArenaRouter::new(players.iter());
+    for event in events.read() {
+        let Ok((mut client, in_lobby)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        if !in_lobby {
+            client.send_chat_message(error("You can pick a class only in the lobby"));
+            continue;
+        }
+        let name = &event.result.class;
+        let class = registry.iter().find(|(_, def)| {
+            def.id.eq_ignore_ascii_case(name) || def.name.eq_ignore_ascii_case(name)
+        });
+        let Some((class, _)) = class else {
+            let classes: Vec<_> = registry.iter().map(|(_, def)| def.id.as_str()).collect();
+            client.send_chat_message(error(format!(
+                "Unknown class, pick one of: {}",
+                classes.join(", ")
+            )));
+            continue;
+        };
+        let Some(arena) = router.pick(arenas.iter()) else {
+            client.send_chat_message(error("All arenas are busy, try again later"));
+            continue;
+        };
+        commands
+            .entity(event.executor)
+            .remove::<LobbyPlayer>()
+            .insert(arena_player_bundle(arena, class, &registry));
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("spectate {arena}")]
+#[scopes("spleef.command.spectate")]
+pub struct SpectateCommand {
+    arena: i32,
+}
+
+pub fn handle_spectate_command(
+    mut events: EventReader<CommandResultEvent<SpectateCommand>>,
+    mut clients: Query<(
+        &mut Client,
+        &mut EntityLayerId,
+        &mut VisibleChunkLayer,
+        &mut VisibleEntityLayers,
+        &mut Position,
+        &mut GameMode,
+        Has<ArenaPlayer>,
+    )>,
+    mut arenas: Query<(Entity, &ArenaNumber, &mut SpawnPoints), With<ArenaLayer>>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok((
+            mut client,
+            mut entity_layer,
+            mut visible_chunk_layer,
+            mut visible_entity_layers,
+            mut pos,
+            mut game_mode,
+            playing,
+        )) = clients.get_mut(event.executor)
+        else {
+            continue;
+        };
+        if playing {
+            client.send_chat_message(error("You're playing, use /leave first"));
+            continue;
+        }
+        let number = event.result.arena;
+        let arena = arenas
+            .iter_mut()
+            .find(|(_, arena_number, _)| arena_number.0 == number);
+        let Some((arena, _, mut spawns)) = arena else {
+            client.send_chat_message(error(format!("There is no arena {number}")));
+            continue;
+        };
+        entity_layer.0 = arena;
+        visible_chunk_layer.0 = arena;
+        visible_entity_layers.0.clear();
+        visible_entity_layers.0.insert(arena);
+
+        pos.set(spawns.next());
+        *game_mode = GameMode::Spectator;
+        commands
+            .entity(event.executor)
+            .remove::<LobbyPlayer>()
+            .insert((
+                Spectator,
+                Watcher,
+                ChunksLoading::default(),
+                KeepPosition(pos.0),
+            ));
+        client.send_chat_message(format!("Spectating arena {number}, /leave to go back"));
+    }
+}
+
+// Arena by number, or the one the executor is in
+fn target_arena<'a>(
+    number: Option<i32>,
+    executor_layer: Entity,
+    mut arenas: impl Iterator<Item = (Entity, &'a ArenaNumber)>,
+) -> Result<Entity, Text> {
+    match number {
+        Some(number) => arenas
+            .find(|(_, arena_number)| arena_number.0 == number)
+            .map(|(arena, _)| arena)
+            .ok_or_else(|| error(format!("There is no arena {number}"))),
+        None => arenas
+            .find(|(arena, _)| *arena == executor_layer)
+            .map(|(arena, _)| arena)
+            .ok_or_else(|| error("You're not in an arena, specify its number")),
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("start {arena?}")]
+#[scopes("spleef.command.start")]
+pub struct StartCommand {
+    arena: Option<i32>,
+}
+
+// Starts the countdown even if there are fewer than min_players
+pub fn handle_start_command(
+    mut events: EventReader<CommandResultEvent<StartCommand>>,
+    mut clients: Query<(&mut Client, &EntityLayerId)>,
+    mut arenas: Query<(Entity, &ArenaNumber, &mut Match, Has<RestoreArena>), With<ArenaLayer>>,
+    players: Query<&EntityLayerId, With<ArenaPlayer>>,
+) {
+    for event in events.read() {
+        let Ok((mut client, executor_layer)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        let arena = target_arena(
+            event.result.arena,
+            executor_layer.0,
+            arenas.iter().map(|(e, number, _, _)| (e, number)),
+        );
+        let arena = match arena {
+            Ok(arena) => arena,
+            Err(message) => {
+                client.send_chat_message(message);
+                continue;
+            }
+        };
+        let Ok((_, number, mut game, restoring)) = arenas.get_mut(arena) else {
+            continue;
+        };
+        if restoring {
+            client.send_chat_message(error("The arena is still being reset"));
+            continue;
+        }
+        if !game.is_starting() {
+            client.send_chat_message(error("The round has already started"));
+            continue;
+        }
+        if !players.iter().any(|layer| layer.0 == arena) {
+            client.send_chat_message(error("Nobody is playing in the arena"));
+            continue;
+        }
+        game.forced = true;
+        if game.phase == MatchPhase::Waiting {
+            game.phase = MatchPhase::Countdown {
+                timer: game.rules.countdown_ticks(),
+            };
+        }
+        client.send_chat_message(format!("Starting arena {}", number.0));
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("stop {arena?}")]
+#[scopes("spleef.command.stop")]
+pub struct StopCommand {
+    arena: Option<i32>,
+}
+
+// Ends the round without a winner, it doesn't count in stats
+pub fn handle_stop_command(
+    mut events: EventReader<CommandResultEvent<StopCommand>>,
+    mut clients: Query<(&mut Client, &EntityLayerId)>,
+    mut arenas: Query<(Entity, &ArenaNumber, &mut Match, &mut ChunkLayer), With<ArenaLayer>>,
+) {
+    for event in events.read() {
+        let Ok((mut client, executor_layer)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        let arena = target_arena(
+            event.result.arena,
+            executor_layer.0,
+            arenas.iter().map(|(e, number, _, _)| (e, number)),
+        );
+        let arena = match arena {
+            Ok(arena) => arena,
+            Err(message) => {
+                client.send_chat_message(message);
+                continue;
+            }
+        };
+        let Ok((_, number, mut game, mut layer)) = arenas.get_mut(arena) else {
+            continue;
+        };
+        if !game.is_running() {
+            client.send_chat_message(error("No round is running"));
+            continue;
+        }
+        game.phase = MatchPhase::Ending {
+            timer: game.rules.ending_ticks(),
+        };
+        layer.send_chat_message("The round was stopped by an operator".color(Color::RED));
+        client.send_chat_message(format!("Stopped arena {}", number.0));
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("reset {arena?}")]
+#[scopes("spleef.command.reset")]
+pub struct ResetCommand {
+    arena: Option<i32>,
+}
+
+// Sends everyone in the arena back to the lobby and restores the blocks
+pub fn handle_reset_command(
+    mut events: EventReader<CommandResultEvent<ResetCommand>>,
+    mut clients: Query<(&mut Client, &EntityLayerId)>,
+    mut arenas: Query<(Entity, &ArenaNumber, &mut Match), With<ArenaLayer>>,
+    participants: Query<(Entity, &EntityLayerId), Or<(With<ArenaPlayer>, With<Spectator>)>>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok((mut client, executor_layer)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        let arena = target_arena(
+            event.result.arena,
+            executor_layer.0,
+            arenas.iter().map(|(e, number, _)| (e, number)),
+        );
+        let arena = match arena {
+            Ok(arena) => arena,
+            Err(message) => {
+                client.send_chat_message(message);
+                continue;
+            }
+        };
+        let Ok((_, number, mut game)) = arenas.get_mut(arena) else {
+            continue;
+        };
+        for (e, _) in participants.iter().filter(|(_, layer)| layer.0 == arena) {
+            commands.entity(e).insert(ReturnToLobby);
+        }
+        commands.entity(arena).insert(RestoreArena::default());
+        game.phase = MatchPhase::Waiting;
+        game.forced = false;
+        client.send_chat_message(format!("Reset arena {}", number.0));
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("arena")]
+#[scopes("spleef.command.arena")]
+pub enum ArenaCommand {
+    #[paths("list")]
+    List,
+}
+
+fn phase_name(phase: MatchPhase) -> &'static str {
+    match phase {
+        MatchPhase::Waiting => "waiting",
+        MatchPhase::Countdown { .. } => "starting",
+        MatchPhase::Running { .. } => "running",
+        MatchPhase::Ending { .. } => "ending",
+    }
+}
+
+pub fn handle_arena_command(
+    mut events: EventReader<CommandResultEvent<ArenaCommand>>,
+    mut clients: Query<&mut Client>,
+    arenas: Query<(Entity, &ArenaNumber, &Match, Has<RestoreArena>), With<ArenaLayer>>,
+    players: Query<&EntityLayerId, With<ArenaPlayer>>,
+) {
+    for event in events.read() {
+        let Ok(mut client) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        match event.result {
+            ArenaCommand::List => {
+                let mut arenas: Vec<_> = arenas.iter().collect();
+                arenas.sort_by_key(|(_, number, _, _)| number.0);
+                let text = arenas.into_iter().fold(
+                    "Arenas".bold(),
+                    |text, (arena, number, game, restoring)| {
+                        let count = players.iter().filter(|layer| layer.0 == arena).count();
+                        let phase = if restoring {
+                            "resetting"
+                        } else {
+                            phase_name(game.phase)
+                        };
+                        text + format!(
+                            "\n{}: {phase}, {count}/{} players",
+                            number.0, game.rules.max_players
+                        )
+                    },
+                );
+                client.send_chat_message(text);
+            }
+        }
+    }
+}
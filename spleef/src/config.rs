@@ -0,0 +1,502 @@
+use crate::{area::Area, level::WOOL, teams::TEAMS};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+};
+use valence::{anvil::parsing::ParseChunkError, prelude::*, DEFAULT_TPS};
+
+pub const CONFIG_PATH: &str = "config.toml";
+
+#[derive(Resource, Deserialize, Debug, Clone)]
+pub struct Config {
+    // Class definitions, see ClassRegistry
+    pub classes: PathBuf,
+    // Player statistics, created on first save
+    pub stats: PathBuf,
+    pub server: ServerConfig,
+    pub lobby: MapConfig,
+    pub arena: ArenaConfig,
+    pub modes: HashMap<String, ModeRules>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServerConfig {
+    pub address: IpAddr,
+    pub port: u16,
+    pub max_players: usize,
+    pub connection_mode: ConnectionModeConfig,
+    // Usernames allowed to use operator commands
+    #[serde(default)]
+    pub operators: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionModeConfig {
+    Online,
+    Offline,
+    BungeeCord,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MapConfig {
+    // World folder with a minigame.toml inside
+    pub map: PathBuf,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArenaConfig {
+    #[serde(flatten)]
+    pub map: MapConfig,
+    pub instances: usize,
+    pub mode: String,
+    // Projectiles are despawned when they leave this area
+    pub projectile_bounds: Area,
+    #[serde(default)]
+    pub effects: EffectsConfig,
+}
+
+// What players see when arena blocks are destroyed
+#[derive(Component, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EffectsConfig {
+    pub falling_blocks: bool,
+    pub particles: bool,
+    pub sounds: bool,
+    // Blocks destroyed after this many in one tick get no effects, so big blasts don't flood clients
+    pub max_per_tick: usize,
+    pub falling_block_seconds: u32,
+}
+
+impl Default for EffectsConfig {
+    fn default() -> Self {
+        Self {
+            falling_blocks: true,
+            particles: true,
+            sounds: true,
+            max_per_tick: 8,
+            falling_block_seconds: 2,
+        }
+    }
+}
+
+impl EffectsConfig {
+    pub fn falling_block_ticks(&self) -> i64 {
+        self.falling_block_seconds as i64 * DEFAULT_TPS.get() as i64
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ModeRules {
+    pub min_players: usize,
+    pub max_players: usize,
+    pub countdown_seconds: u32,
+    pub ending_seconds: u32,
+    // Round ends without a winner after this many seconds
+    #[serde(default)]
+    pub round_seconds: Option<u32>,
+    // Destroyed blocks come back after this many seconds, for practice arenas
+    #[serde(default)]
+    pub regeneration_seconds: Option<u32>,
+    // There's no last player standing, the match goes on until everyone is out
+    // or time is up, for practice arenas
+    #[serde(default)]
+    pub endless: bool,
+    // Players are split into this many teams, each owning a wool colour
+    #[serde(default)]
+    pub teams: Option<usize>,
+    // Plays Block Party on the arena floor instead of spleef
+    #[serde(default)]
+    pub block_party: Option<BlockPartyRules>,
+    // Blocks crumble shortly after being stepped on, even if the player moves on
+    #[serde(default)]
+    pub tnt_run: Option<TntRunRules>,
+    // Knockback only on the arena floor, played in several rounds
+    #[serde(default)]
+    pub sumo: Option<SumoRules>,
+    // Blocks are only broken by projectiles, everyone gets an egg launcher
+    #[serde(default)]
+    pub splegg: Option<SpleggRules>,
+    #[serde(default)]
+    pub knockback: KnockbackRules,
+}
+
+// Velocity given to players hit in melee
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KnockbackRules {
+    pub horizontal: f32,
+    pub vertical: f32,
+    // Used when the attacker is sprinting
+    pub sprint_horizontal: f32,
+    pub sprint_vertical: f32,
+    // Horizontal knockback of projectile hits, relative to horizontal
+    pub arrow_multiplier: f32,
+    pub fireball_multiplier: f32,
+}
+
+impl Default for KnockbackRules {
+    fn default() -> Self {
+        Self {
+            horizontal: 8.0,
+            vertical: 6.432,
+            sprint_horizontal: 18.0,
+            sprint_vertical: 8.432,
+            arrow_multiplier: 1.0,
+            fireball_multiplier: 1.5,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SpleggRules {
+    // Fire rate of the launcher
+    pub cooldown_ticks: i64,
+    pub speed: f32,
+    // Snowballs used as ammo, unlimited if not set
+    pub ammo: Option<i8>,
+    // Players at the edge of the arena get a snowball back this often
+    pub edge_refill_seconds: Option<u32>,
+    // Distance from the arena border that counts as the edge
+    pub edge_width: f64,
+}
+
+impl Default for SpleggRules {
+    fn default() -> Self {
+        Self {
+            cooldown_ticks: 5,
+            speed: 30.0,
+            ammo: None,
+            edge_refill_seconds: None,
+            edge_width: 2.0,
+        }
+    }
+}
+
+impl SpleggRules {
+    pub fn edge_refill_ticks(&self) -> Option<i64> {
+        self.edge_refill_seconds
+            .map(|seconds| seconds as i64 * DEFAULT_TPS.get() as i64)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SumoRules {
+    // The match is won by winning the majority of this many rounds
+    pub best_of: u32,
+    // Pause between rounds
+    pub intermission_seconds: u32,
+}
+
+impl Default for SumoRules {
+    fn default() -> Self {
+        Self {
+            best_of: 3,
+            intermission_seconds: 3,
+        }
+    }
+}
+
+impl SumoRules {
+    pub fn wins_needed(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    pub fn intermission_ticks(&self) -> i64 {
+        self.intermission_seconds as i64 * DEFAULT_TPS.get() as i64
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TntRunRules {
+    // Ticks between stepping on a block and it disappearing
+    pub delay_ticks: u16,
+}
+
+impl Default for TntRunRules {
+    fn default() -> Self {
+        Self { delay_ticks: 10 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BlockPartyRules {
+    // Time to find the colour in the first round
+    pub first_round_seconds: f32,
+    // Every round is this many times shorter than the previous one
+    pub shrink: f32,
+    pub min_round_seconds: f32,
+    // How long only the right colour is left before the next round
+    pub removed_seconds: f32,
+    // Wool colours in one floor pattern
+    pub colors: usize,
+}
+
+impl Default for BlockPartyRules {
+    fn default() -> Self {
+        Self {
+            first_round_seconds: 5.0,
+            shrink: 0.85,
+            min_round_seconds: 1.0,
+            removed_seconds: 3.0,
+            colors: 6,
+        }
+    }
+}
+
+impl BlockPartyRules {
+    // Rounds start from 1
+    pub fn round_ticks(&self, round: u32) -> i64 {
+        let seconds = self.first_round_seconds * self.shrink.powi(round as i32 - 1);
+        (seconds.max(self.min_round_seconds) * DEFAULT_TPS.get() as f32) as i64
+    }
+
+    pub fn removed_ticks(&self) -> i64 {
+        (self.removed_seconds * DEFAULT_TPS.get() as f32) as i64
+    }
+}
+
+impl ModeRules {
+    pub fn countdown_ticks(&self) -> i64 {
+        self.countdown_seconds as i64 * DEFAULT_TPS.get() as i64
+    }
+
+    pub fn ending_ticks(&self) -> i64 {
+        self.ending_seconds as i64 * DEFAULT_TPS.get() as i64
+    }
+
+    pub fn round_ticks(&self) -> Option<i64> {
+        self.round_seconds
+            .map(|seconds| seconds as i64 * DEFAULT_TPS.get() as i64)
+    }
+
+    pub fn regeneration_ticks(&self) -> Option<i64> {
+        self.regeneration_seconds
+            .map(|seconds| seconds as i64 * DEFAULT_TPS.get() as i64)
+    }
+
+    fn validate(&self, mode: &str) -> Result<(), ConfigError> {
+        if self.min_players == 0 || self.min_players > self.max_players {
+            return Err(ConfigError::Invalid(format!(
+                "mode {:?} needs 1 <= min_players <= max_players",
+                mode
+            )));
+        }
+        // a single player would win the moment the match starts
+        if self.min_players < 2 && !self.endless {
+            return Err(ConfigError::Invalid(format!(
+                "mode {:?} needs min_players >= 2 unless it's endless",
+                mode
+            )));
+        }
+        if self
+            .teams
+            .is_some_and(|teams| teams < 2 || teams > TEAMS.len())
+        {
+            return Err(ConfigError::Invalid(format!(
+                "mode {:?} needs 2 <= teams <= {}",
+                mode,
+                TEAMS.len()
+            )));
+        }
+        if self
+            .tnt_run
+            .as_ref()
+            .is_some_and(|tnt| tnt.delay_ticks == 0)
+        {
+            return Err(ConfigError::Invalid(format!(
+                "mode {:?} needs tnt_run.delay_ticks >= 1",
+                mode
+            )));
+        }
+        if self.sumo.as_ref().is_some_and(|sumo| sumo.best_of == 0) {
+            return Err(ConfigError::Invalid(format!(
+                "mode {:?} needs sumo.best_of >= 1",
+                mode
+            )));
+        }
+        if let Some(splegg) = &self.splegg {
+            // snowballs stack to 16
+            if splegg.ammo.is_some_and(|ammo| !(1..=16).contains(&ammo)) {
+                return Err(ConfigError::Invalid(format!(
+                    "mode {:?} needs 1 <= splegg.ammo <= 16",
+                    mode
+                )));
+            }
+            if splegg.edge_refill_seconds == Some(0) {
+                return Err(ConfigError::Invalid(format!(
+                    "mode {:?} needs splegg.edge_refill_seconds >= 1",
+                    mode
+                )));
+            }
+        }
+        if let Some(party) = &self.block_party {
+            if party.colors < 2 || party.colors > WOOL.len() {
+                return Err(ConfigError::Invalid(format!(
+                    "mode {:?} needs 2 <= block_party.colors <= {}",
+                    mode,
+                    WOOL.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Map(PathBuf, ChunkPos, ParseChunkError),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "can't read {}: {err}", path.display()),
+            ConfigError::Parse(path, err) => write!(f, "can't parse {}: {err}", path.display()),
+            ConfigError::Map(path, pos, err) => write!(
+                f,
+                "can't load chunk {} {} of {}: {err}",
+                pos.x,
+                pos.z,
+                path.display()
+            ),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let data =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_owned(), e))?;
+        let config: Config =
+            toml::from_str(&data).map_err(|e| ConfigError::Parse(path.to_owned(), e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        for map in [&self.lobby, &self.arena.map] {
+            if !map.map.join("region").is_dir() {
+                return Err(ConfigError::Invalid(format!(
+                    "{} is not a minecraft world folder",
+                    map.map.display()
+                )));
+            }
+        }
+        if self.arena.instances == 0 {
+            return Err(ConfigError::Invalid(
+                "arena.instances must be at least 1".into(),
+            ));
+        }
+        let rules = self.arena_rules().ok_or_else(|| {
+            ConfigError::Invalid(format!("mode {:?} is not defined", self.arena.mode))
+        })?;
+        rules.validate(&self.arena.mode)
+    }
+
+    pub fn arena_rules(&self) -> Option<&ModeRules> {
+        self.modes.get(&self.arena.mode)
+    }
+
+    pub fn network_settings(&self) -> NetworkSettings {
+        let connection_mode = match self.server.connection_mode {
+            ConnectionModeConfig::Online => ConnectionMode::Online {
+                prevent_proxy_connections: false,
+            },
+            ConnectionModeConfig::Offline => ConnectionMode::Offline,
+            ConnectionModeConfig::BungeeCord => ConnectionMode::BungeeCord,
+        };
+        NetworkSettings {
+            address: SocketAddr::new(self.server.address, self.server.port),
+            max_players: self.server.max_players,
+            connection_mode,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(extra: &str) -> ModeRules {
+        let base = "min_players = 2\nmax_players = 8\ncountdown_seconds = 10\nending_seconds = 5\n";
+        toml::from_str(&format!("{base}{extra}")).unwrap()
+    }
+
+    fn is_valid(extra: &str) -> bool {
+        rules(extra).validate("test").is_ok()
+    }
+
+    #[test]
+    fn default_rules_are_valid() {
+        assert!(is_valid(""));
+        assert!(is_valid("[tnt_run]\n"));
+        assert!(is_valid("[sumo]\n"));
+        assert!(is_valid("[splegg]\n"));
+        assert!(is_valid("[block_party]\n"));
+    }
+
+    #[test]
+    fn player_counts() {
+        let mut mode = rules("");
+        mode.min_players = 0;
+        assert!(mode.validate("test").is_err());
+        mode.min_players = 9;
+        assert!(mode.validate("test").is_err());
+        mode.min_players = 8;
+        assert!(mode.validate("test").is_ok());
+        // alone only in practice
+        mode.min_players = 1;
+        assert!(mode.validate("test").is_err());
+        mode.endless = true;
+        assert!(mode.validate("test").is_ok());
+    }
+
+    #[test]
+    fn teams() {
+        assert!(!is_valid("teams = 1"));
+        assert!(is_valid("teams = 2"));
+        assert!(is_valid(&format!("teams = {}", TEAMS.len())));
+        assert!(!is_valid(&format!("teams = {}", TEAMS.len() + 1)));
+    }
+
+    #[test]
+    fn mode_settings() {
+        assert!(!is_valid("[tnt_run]\ndelay_ticks = 0"));
+        assert!(!is_valid("[sumo]\nbest_of = 0"));
+        assert!(is_valid("[sumo]\nbest_of = 1"));
+        assert!(!is_valid("[splegg]\nammo = 0"));
+        assert!(!is_valid("[splegg]\nammo = 17"));
+        assert!(is_valid("[splegg]\nammo = 16"));
+        assert!(!is_valid("[splegg]\nedge_refill_seconds = 0"));
+        assert!(!is_valid("[block_party]\ncolors = 1"));
+        assert!(is_valid(&format!("[block_party]\ncolors = {}", WOOL.len())));
+        assert!(!is_valid(&format!(
+            "[block_party]\ncolors = {}",
+            WOOL.len() + 1
+        )));
+    }
+
+    #[test]
+    fn errors_name_the_mode() {
+        let Err(ConfigError::Invalid(reason)) = rules("teams = 1").validate("spleef") else {
+            panic!("teams = 1 should be invalid");
+        };
+        assert!(reason.contains("\"spleef\""), "{reason}");
+    }
+}
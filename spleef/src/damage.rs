@@ -0,0 +1,89 @@
+use crate::{dynamic_blocks::DynamicBlocks, teams::Team};
+use valence::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageCause {
+    // Player standing on the block
+    Standing,
+    Dig,
+    Arrow,
+    Fireball,
+    Egg,
+}
+
+impl DamageCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            DamageCause::Standing => "standing",
+            DamageCause::Dig => "dig",
+            DamageCause::Arrow => "arrow",
+            DamageCause::Fireball => "fireball",
+            DamageCause::Egg => "egg",
+        }
+    }
+}
+
+// Every way of breaking arena blocks goes through this event
+// Blocks that aren't in DynamicBlocks ignore it
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BlockDamageEvent {
+    pub layer: Entity,
+    pub pos: BlockPos,
+    pub amount: u16,
+    // Player responsible for the damage
+    pub source: Entity,
+    pub cause: DamageCause,
+}
+
+impl BlockDamageEvent {
+    // Breaks the block no matter how much hp it has left
+    pub const DESTROY: u16 = u16::MAX;
+}
+
+// Sent after the block is replaced with air
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BlockDestroyedEvent {
+    pub layer: Entity,
+    pub pos: BlockPos,
+    // Block before it was destroyed
+    pub state: BlockState,
+    pub source: Entity,
+    pub cause: DamageCause,
+}
+
+pub fn apply_block_damage(
+    mut events: EventReader<BlockDamageEvent>,
+    mut layers: Query<(&mut ChunkLayer, &mut DynamicBlocks)>,
+    teams: Query<&Team>,
+    mut destroyed: EventWriter<BlockDestroyedEvent>,
+) {
+    for event in events.read() {
+        let Ok((mut layer, mut dynamic)) = layers.get_mut(event.layer) else {
+            continue;
+        };
+        // teams can't break their own colour
+        let own_wool = teams.get(event.source).is_ok_and(|team| {
+            layer
+                .block(event.pos)
+                .is_some_and(|block| block.state == team.def().wool)
+        });
+        if own_wool {
+            continue;
+        }
+        if dynamic.damage(event.pos, event.amount) != Some(0) {
+            continue;
+        }
+
+        let state = layer
+            .block(event.pos)
+            .map_or(BlockState::AIR, |block| block.state);
+        layer.set_block(event.pos, BlockState::AIR);
+        destroyed.send(BlockDestroyedEvent {
+            layer: event.layer,
+            pos: event.pos,
+            state,
+            source: event.source,
+            cause: event.cause,
+        });
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use valence::{prelude::Component, BlockPos, ChunkPos};
+
+const SECTION_BLOCKS: usize = 16 * 16 * 16;
+
+// Hp of every block in a 16x16x16 chunk section, 0 means the block isn't dynamic
+struct Section {
+    hp: Box<[u16]>,
+    // Bit per block that is already in DynamicBlocks::dirty
+    dirty: Box<[u64]>,
+}
+
+impl Section {
+    fn new() -> Self {
+        Self {
+            hp: vec![0; SECTION_BLOCKS].into_boxed_slice(),
+            dirty: vec![0; SECTION_BLOCKS / 64].into_boxed_slice(),
+        }
+    }
+
+    // Returns false if the block was already marked
+    fn mark_dirty(&mut self, index: usize) -> bool {
+        let bit = 1 << (index % 64);
+        let word = &mut self.dirty[index / 64];
+        let marked = *word & bit != 0;
+        *word |= bit;
+        !marked
+    }
+
+    fn clear_dirty(&mut self, index: usize) {
+        self.dirty[index / 64] &= !(1 << (index % 64));
+    }
+}
+
+#[derive(Default)]
+struct Column {
+    // Section y of sections[0]
+    bottom: i32,
+    sections: Vec<Option<Section>>,
+}
+
+impl Column {
+    fn section(&self, section_y: i32) -> Option<&Section> {
+        let index = usize::try_from(section_y - self.bottom).ok()?;
+        self.sections.get(index)?.as_ref()
+    }
+
+    fn section_mut(&mut self, section_y: i32) -> Option<&mut Section> {
+        let index = usize::try_from(section_y - self.bottom).ok()?;
+        self.sections.get_mut(index)?.as_mut()
+    }
+
+    fn section_or_insert(&mut self, section_y: i32) -> &mut Section {
+        if self.sections.is_empty() {
+            self.bottom = section_y;
+        }
+        if section_y < self.bottom {
+            let missing = (self.bottom - section_y) as usize;
+            self.sections
+                .splice(0..0, std::iter::repeat_with(|| None).take(missing));
+            self.bottom = section_y;
+        }
+        let index = (section_y - self.bottom) as usize;
+        if index >= self.sections.len() {
+            self.sections.resize_with(index + 1, || None);
+        }
+        self.sections[index].get_or_insert_with(Section::new)
+    }
+}
+
+fn split(pos: BlockPos) -> (ChunkPos, i32, usize) {
+    let index = pos.y.rem_euclid(16) * 256 + pos.z.rem_euclid(16) * 16 + pos.x.rem_euclid(16);
+    (pos.into(), pos.y.div_euclid(16), index as usize)
+}
+
+// Blocks of an arena that can be broken, with their remaining hp
+// Stored densely per chunk section, so lookups every tick stay cheap on big arenas
+#[derive(Component, Default)]
+pub struct DynamicBlocks {
+    columns: HashMap<ChunkPos, Column>,
+    // Blocks with cracks that changed since the last drain_dirty
+    dirty: Vec<BlockPos>,
+    // Clients keep one breaking animation per entity id, so every cracked block gets its own
+    breakers: HashMap<BlockPos, i32>,
+    // Ids of blocks whose cracks were cleared, reused before new ones
+    free_breakers: Vec<i32>,
+    last_breaker: i32,
+}
+
+impl DynamicBlocks {
+    pub fn hp(&self, pos: BlockPos) -> Option<u16> {
+        let (chunk, section_y, index) = split(pos);
+        let hp = self.columns.get(&chunk)?.section(section_y)?.hp[index];
+        (hp > 0).then_some(hp)
+    }
+
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        self.hp(pos).is_some()
+    }
+
+    // Makes the block dynamic with full hp, or heals it
+    pub fn insert(&mut self, pos: BlockPos, hp: u16) {
+        self.set(pos, hp);
+    }
+
+    // Returns the remaining hp, the block is removed when it reaches 0
+    pub fn damage(&mut self, pos: BlockPos, amount: u16) -> Option<u16> {
+        let hp = self.hp(pos)?.saturating_sub(amount);
+        self.set(pos, hp);
+        Some(hp)
+    }
+
+    fn set(&mut self, pos: BlockPos, hp: u16) {
+        let (chunk, section_y, index) = split(pos);
+        let section = if hp == 0 {
+            // don't allocate sections just to remove something
+            match self
+                .columns
+                .get_mut(&chunk)
+                .and_then(|column| column.section_mut(section_y))
+            {
+                Some(section) => section,
+                None => return,
+            }
+        } else {
+            self.columns
+                .entry(chunk)
+                .or_default()
+                .section_or_insert(section_y)
+        };
+        let old = std::mem::replace(&mut section.hp[index], hp);
+        // fresh blocks have no cracks to update
+        if old > 0 && old != hp && section.mark_dirty(index) {
+            self.dirty.push(pos);
+        }
+    }
+
+    // Blocks with changed cracks and their current hp, 0 if they were removed
+    pub fn drain_dirty(&mut self) -> impl Iterator<Item = (BlockPos, u16)> + '_ {
+        let dirty = std::mem::take(&mut self.dirty);
+        dirty.into_iter().map(move |pos| {
+            let (chunk, section_y, index) = split(pos);
+            let hp = match self
+                .columns
+                .get_mut(&chunk)
+                .and_then(|column| column.section_mut(section_y))
+            {
+                Some(section) => {
+                    section.clear_dirty(index);
+                    section.hp[index]
+                }
+                None => 0,
+            };
+            (pos, hp)
+        })
+    }
+
+    // Entity id the block's cracks are sent with, kept until release_breaker
+    // Negative, so it never collides with real entities
+    pub fn breaker_id(&mut self, pos: BlockPos) -> i32 {
+        if let Some(&id) = self.breakers.get(&pos) {
+            return id;
+        }
+        let id = self.free_breakers.pop().unwrap_or_else(|| {
+            self.last_breaker -= 1;
+            self.last_breaker
+        });
+        self.breakers.insert(pos, id);
+        id
+    }
+
+    // Call once the block's cracks are cleared
+    pub fn release_breaker(&mut self, pos: BlockPos) {
+        if let Some(id) = self.breakers.remove(&pos) {
+            self.free_breakers.push(id);
+        }
+    }
+}
+
+// Breaking animation stage, 10 and above clears the cracks
+pub fn destroy_stage(hp: u16, max_hp: u16) -> u8 {
+    if hp == 0 || max_hp == 0 {
+        return 10;
+    }
+    let hp = hp.min(max_hp) as u32;
+    let max_hp = max_hp as u32;
+    let stage = (max_hp - hp) * 11 / max_hp;
+    if stage == 0 {
+        10
+    } else {
+        stage as u8 - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_damage() {
+        let mut blocks = DynamicBlocks::default();
+        let pos = BlockPos::new(3, 64, -5);
+        assert!(!blocks.contains(pos));
+        blocks.insert(pos, 10);
+        assert_eq!(blocks.hp(pos), Some(10));
+        assert_eq!(blocks.damage(pos, 4), Some(6));
+        assert_eq!(blocks.damage(pos, 100), Some(0));
+        assert!(!blocks.contains(pos));
+        assert_eq!(blocks.damage(pos, 1), None);
+    }
+
+    #[test]
+    fn blocks_in_neighbouring_sections_are_separate() {
+        let mut blocks = DynamicBlocks::default();
+        let positions = [
+            BlockPos::new(0, 0, 0),
+            BlockPos::new(-1, 0, 0),
+            BlockPos::new(15, 15, 15),
+            BlockPos::new(16, 16, 16),
+            BlockPos::new(0, -1, 0),
+            BlockPos::new(0, -64, 0),
+            BlockPos::new(0, 319, 0),
+            BlockPos::new(-17, 5, -33),
+        ];
+        for (i, &pos) in positions.iter().enumerate() {
+            blocks.insert(pos, i as u16 + 1);
+        }
+        for (i, &pos) in positions.iter().enumerate() {
+            assert_eq!(blocks.hp(pos), Some(i as u16 + 1));
+        }
+        assert!(!blocks.contains(BlockPos::new(1, 0, 0)));
+        assert!(!blocks.contains(BlockPos::new(0, -128, 0)));
+    }
+
+    #[test]
+    fn removing_unknown_blocks_allocates_nothing() {
+        let mut blocks = DynamicBlocks::default();
+        blocks.insert(BlockPos::new(0, 0, 0), 0);
+        assert!(blocks.columns.is_empty());
+    }
+
+    #[test]
+    fn dirty_blocks_are_reported_once() {
+        let mut blocks = DynamicBlocks::default();
+        let a = BlockPos::new(1, 2, 3);
+        let b = BlockPos::new(-40, 2, 3);
+        blocks.insert(a, 10);
+        blocks.insert(b, 10);
+        // fresh blocks have no cracks
+        assert_eq!(blocks.drain_dirty().count(), 0);
+
+        blocks.damage(a, 1);
+        blocks.damage(a, 1);
+        blocks.damage(b, 10);
+        let dirty: Vec<_> = blocks.drain_dirty().collect();
+        assert_eq!(dirty, vec![(a, 8), (b, 0)]);
+        assert_eq!(blocks.drain_dirty().count(), 0);
+
+        // healing is a change of cracks too
+        blocks.insert(a, 10);
+        let dirty: Vec<_> = blocks.drain_dirty().collect();
+        assert_eq!(dirty, vec![(a, 10)]);
+    }
+
+    #[test]
+    fn breaker_ids_are_unique_and_reused() {
+        let mut blocks = DynamicBlocks::default();
+        let a = BlockPos::new(0, 0, 0);
+        let b = BlockPos::new(1, 0, 0);
+        let id_a = blocks.breaker_id(a);
+        let id_b = blocks.breaker_id(b);
+        assert!(id_a < 0 && id_b < 0);
+        assert_ne!(id_a, id_b);
+        assert_eq!(blocks.breaker_id(a), id_a);
+
+        blocks.release_breaker(a);
+        let c = BlockPos::new(2, 0, 0);
+        assert_eq!(blocks.breaker_id(c), id_a);
+        assert_ne!(blocks.breaker_id(a), id_a);
+    }
+
+    #[test]
+    fn destroy_stages() {
+        assert_eq!(destroy_stage(100, 100), 10);
+        assert_eq!(destroy_stage(0, 100), 10);
+        assert_eq!(destroy_stage(1, 0), 10);
+        assert_eq!(destroy_stage(50, 100), 4);
+        assert_eq!(destroy_stage(1, 100), 9);
+        assert_eq!(destroy_stage(200, 100), 10);
+    }
+}
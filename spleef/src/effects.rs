@@ -0,0 +1,114 @@
+use crate::{config::EffectsConfig, damage::BlockDestroyedEvent, game::ArenaBounds};
+use std::collections::HashMap;
+use valence::{
+    entity::{falling_block::FallingBlockEntityBundle, ObjectData, Velocity},
+    particle::Particle,
+    prelude::*,
+    sound::{Sound, SoundCategory},
+};
+
+// Falling block left by a destroyed arena block
+#[derive(Component)]
+pub struct Debris {
+    despawn_tick: i64,
+}
+
+// Same sound group the client uses, stone for anything unknown
+fn break_sound(state: BlockState) -> Sound {
+    let kind = state.to_kind();
+    match kind {
+        BlockKind::Snow | BlockKind::SnowBlock => Sound::BlockSnowBreak,
+        BlockKind::Ice | BlockKind::PackedIce | BlockKind::BlueIce | BlockKind::FrostedIce => {
+            Sound::BlockGlassBreak
+        }
+        BlockKind::GoldBlock
+        | BlockKind::IronBlock
+        | BlockKind::DiamondBlock
+        | BlockKind::EmeraldBlock => Sound::BlockMetalBreak,
+        _ if kind.to_str().ends_with("wool") => Sound::BlockWoolBreak,
+        _ if kind.to_str().ends_with("glass") => Sound::BlockGlassBreak,
+        _ => Sound::BlockStoneBreak,
+    }
+}
+
+pub fn spawn_destroy_effects(
+    server: Res<Server>,
+    mut destroyed: EventReader<BlockDestroyedEvent>,
+    mut arenas: Query<(&mut ChunkLayer, &EffectsConfig)>,
+    mut commands: Commands,
+) {
+    let mut spawned: HashMap<Entity, usize> = HashMap::new();
+    for event in destroyed.read() {
+        let Ok((mut layer, effects)) = arenas.get_mut(event.layer) else {
+            continue;
+        };
+        let count = spawned.entry(event.layer).or_default();
+        if *count >= effects.max_per_tick {
+            continue;
+        }
+        *count += 1;
+
+        let pos = event.pos;
+        let center = DVec3::new(pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5);
+        if effects.particles {
+            layer.play_particle(
+                &Particle::Block(event.state),
+                false,
+                center,
+                [0.25, 0.25, 0.25],
+                0.0,
+                12,
+            );
+        }
+        if effects.sounds {
+            layer.play_sound(
+                break_sound(event.state),
+                SoundCategory::Block,
+                center,
+                1.0,
+                1.0,
+            );
+        }
+        if effects.falling_blocks {
+            commands.spawn((
+                FallingBlockEntityBundle {
+                    object_data: ObjectData(event.state.to_raw() as i32),
+                    position: Position([center.x, pos.y as f64, center.z].into()),
+                    layer: EntityLayerId(event.layer),
+                    ..Default::default()
+                },
+                Debris {
+                    despawn_tick: server.current_tick() + effects.falling_block_ticks(),
+                },
+            ));
+        }
+    }
+}
+
+pub fn update_debris(
+    server: Res<Server>,
+    mut debris: Query<(
+        Entity,
+        &Debris,
+        &mut Position,
+        &mut Velocity,
+        &EntityLayerId,
+    )>,
+    arenas: Query<&ArenaBounds>,
+    mut commands: Commands,
+) {
+    for (e, debris, mut pos, mut vel, layer) in debris.iter_mut() {
+        // same gravity and drag the client uses for falling blocks
+        vel.0.y -= 0.8;
+        vel.0 *= 0.98;
+        let step: DVec3 = vel.0.into();
+        pos.0 += step / 20.0;
+
+        let in_bounds = arenas
+            .get(layer.0)
+            .is_ok_and(|bounds| bounds.area.contains(pos.0));
+        if !in_bounds || server.current_tick() >= debris.despawn_tick {
+            commands.entity(e).insert(Despawned);
+        }
+    }
+}
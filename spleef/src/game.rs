@@ -0,0 +1,448 @@
+use crate::{
+    area::Area,
+    classes::{ClassComponents, ClassId, CombatState, PlayerClass},
+    config::ModeRules,
+    level::{ArenaLayer, ArenaPlayer, RestoreArena, ReturnToLobby},
+    sumo::{Sumo, SumoRound},
+    teams::Team,
+};
+use std::collections::HashSet;
+use valence::{prelude::*, DEFAULT_TPS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchPhase {
+    // Not enough players to start the round
+    Waiting,
+    // Round starts when timer reaches zero
+    Countdown { timer: i64 },
+    Running { elapsed: i64 },
+    // Winner is announced, new players can't join until timer reaches zero
+    Ending { timer: i64 },
+}
+
+#[derive(Component)]
+pub struct Match {
+    pub phase: MatchPhase,
+    pub rules: ModeRules,
+    // Started with /start, doesn't wait for min_players
+    pub forced: bool,
+}
+
+impl Match {
+    pub fn new(rules: ModeRules) -> Self {
+        Self {
+            phase: MatchPhase::Waiting,
+            rules,
+            forced: false,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.phase, MatchPhase::Running { .. })
+    }
+
+    pub fn is_starting(&self) -> bool {
+        matches!(
+            self.phase,
+            MatchPhase::Waiting | MatchPhase::Countdown { .. }
+        )
+    }
+
+    // Practice arenas heal and keep going, so anyone can hop in
+    pub fn is_joinable(&self) -> bool {
+        self.is_starting() || (self.rules.endless && self.is_running())
+    }
+}
+
+// Sent when a running match is over
+#[derive(Event, Debug, Clone)]
+pub struct MatchEndedEvent {
+    pub arena: Entity,
+    // The whole team in team modes, eliminated teammates included
+    pub winners: Vec<Entity>,
+    // Everyone who played, including eliminated players
+    pub participants: Vec<Entity>,
+    // Players still standing when the match ended, even if time ran out
+    pub survivors: Vec<Entity>,
+    pub elapsed: i64,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerEliminatedEvent {
+    pub arena: Entity,
+    pub player: Entity,
+    // Last player who hit them, if it was recent
+    pub attacker: Option<Entity>,
+    pub class: ClassId,
+    pub survived_ticks: i64,
+}
+
+// Sent when a player or eliminated player leaves a running match with /leave,
+// they aren't a participant when it ends
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerLeftMatchEvent {
+    pub arena: Entity,
+    pub player: Entity,
+}
+
+// Leaving a running match counts as losing it, and as being eliminated
+// for players who were still in the game
+// None if there's no running match to leave
+pub fn leave_match(
+    game: &Match,
+    arena: Entity,
+    player: Entity,
+    combat: Option<&CombatState>,
+    class: Option<&PlayerClass>,
+    current_tick: i64,
+) -> Option<(PlayerLeftMatchEvent, Option<PlayerEliminatedEvent>)> {
+    let MatchPhase::Running { elapsed } = game.phase else {
+        return None;
+    };
+    // eliminated players already lost their class
+    let eliminated = combat
+        .zip(class)
+        .map(|(combat, class)| PlayerEliminatedEvent {
+            arena,
+            player,
+            attacker: combat.recent_attacker(current_tick),
+            class: class.0,
+            survived_ticks: elapsed,
+        });
+    Some((PlayerLeftMatchEvent { arena, player }, eliminated))
+}
+
+// Disconnecting is the same as /leave
+// Valence only removes Client, the entity is despawned here once the stats are sent
+pub fn leave_on_disconnect(
+    server: Res<Server>,
+    mut disconnected: RemovedComponents<Client>,
+    players: Query<
+        (
+            &Username,
+            &EntityLayerId,
+            Option<&CombatState>,
+            Option<&PlayerClass>,
+        ),
+        (Or<(With<ArenaPlayer>, With<Spectator>)>, Without<Watcher>),
+    >,
+    mut arenas: Query<(&Match, &mut ChunkLayer), With<ArenaLayer>>,
+    mut eliminated: EventWriter<PlayerEliminatedEvent>,
+    mut left: EventWriter<PlayerLeftMatchEvent>,
+    mut commands: Commands,
+) {
+    for player in disconnected.read() {
+        let Some(mut entity) = commands.get_entity(player) else {
+            continue;
+        };
+        entity.insert(Despawned);
+        let Ok((username, layer_id, combat, class)) = players.get(player) else {
+            continue;
+        };
+        let Ok((game, mut layer)) = arenas.get_mut(layer_id.0) else {
+            continue;
+        };
+        let Some((left_event, eliminated_event)) = leave_match(
+            game,
+            layer_id.0,
+            player,
+            combat,
+            class,
+            server.current_tick(),
+        ) else {
+            continue;
+        };
+        left.send(left_event);
+        if let Some(eliminated_event) = eliminated_event {
+            layer.send_chat_message(username.0.clone().bold() + " disconnected");
+            eliminated.send(eliminated_event);
+        }
+    }
+}
+
+// False for layers without a match, e.g. the lobby
+pub fn is_running(matches: &Query<&Match>, layer: &EntityLayerId) -> bool {
+    matches.get(layer.0).is_ok_and(Match::is_running)
+}
+
+pub fn update_match(
+    mut arenas: Query<
+        (
+            Entity,
+            &mut Match,
+            &mut ChunkLayer,
+            Has<RestoreArena>,
+            Option<&mut Sumo>,
+        ),
+        With<ArenaLayer>,
+    >,
+    players: Query<(Entity, &Username, Option<&Team>, &EntityLayerId), With<ArenaPlayer>>,
+    participants: Query<
+        (Entity, Option<&Team>, &EntityLayerId),
+        (Or<(With<ArenaPlayer>, With<Spectator>)>, Without<Watcher>),
+    >,
+    mut ended: EventWriter<MatchEndedEvent>,
+    mut commands: Commands,
+) {
+    for (arena_id, mut game, mut layer, restoring, mut sumo) in arenas.iter_mut() {
+        let players: Vec<_> = players
+            .iter()
+            .filter(|(_, _, _, layer_id)| layer_id.0 == arena_id)
+            .map(|(e, username, team, _)| (e, username, team.copied()))
+            .collect();
+        let participants: Vec<_> = participants
+            .iter()
+            .filter(|(_, _, layer_id)| layer_id.0 == arena_id)
+            .map(|(e, team, _)| (e, team.copied()))
+            .collect();
+        update_match_phase(
+            arena_id,
+            &mut game,
+            &mut layer,
+            restoring,
+            sumo.as_deref_mut(),
+            &players,
+            participants,
+            &mut ended,
+            &mut commands,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_match_phase(
+    arena_id: Entity,
+    game: &mut Match,
+    layer: &mut ChunkLayer,
+    restoring: bool,
+    sumo: Option<&mut Sumo>,
+    players: &[(Entity, &Username, Option<Team>)],
+    participants: Vec<(Entity, Option<Team>)>,
+    ended: &mut EventWriter<MatchEndedEvent>,
+    commands: &mut Commands,
+) {
+    let tps = DEFAULT_TPS.get() as i64;
+    game.phase = match game.phase {
+        MatchPhase::Waiting => {
+            if restoring || players.len() < game.rules.min_players {
+                return;
+            }
+            MatchPhase::Countdown {
+                timer: game.rules.countdown_ticks(),
+            }
+        }
+        MatchPhase::Countdown { timer } => {
+            if players.is_empty() || (!game.forced && players.len() < game.rules.min_players) {
+                game.forced = false;
+                layer
+                    .send_chat_message("Not enough players, countdown cancelled".color(Color::RED));
+                MatchPhase::Waiting
+            } else if timer <= 0 {
+                layer.send_chat_message("Go!".bold());
+                MatchPhase::Running { elapsed: 0 }
+            } else {
+                if timer % tps == 0 {
+                    layer.send_chat_message(format!("Round starts in {}...", timer / tps));
+                }
+                MatchPhase::Countdown { timer: timer - 1 }
+            }
+        }
+        MatchPhase::Running { elapsed } => {
+            let time_is_up = game
+                .rules
+                .round_ticks()
+                .is_some_and(|round| elapsed >= round);
+            // in team modes the round goes on while two teams have players left
+            let sides: HashSet<_> = players.iter().map(|(e, _, team)| team.ok_or(*e)).collect();
+            let winner = players.first().filter(|_| !time_is_up);
+            // sumo players are brought back between rounds, while any of them are still here
+            let sumo_round = match (sumo, &game.rules.sumo) {
+                (Some(sumo), Some(rules)) if !time_is_up && !participants.is_empty() => {
+                    Some(sumo.check_round(sides.len(), winner.map(|(e, _, _)| *e), rules))
+                }
+                _ => None,
+            };
+            let keep_running = match sumo_round {
+                Some(SumoRound::Playing) => true,
+                Some(SumoRound::Won { round, wins }) => {
+                    if let (Some((_, username, _)), Some(rules)) = (winner, &game.rules.sumo) {
+                        layer.send_chat_message(
+                            username.0.clone().bold()
+                                + format!(
+                                    " won round {round} ({wins}/{} wins)",
+                                    rules.wins_needed()
+                                ),
+                        );
+                    }
+                    true
+                }
+                Some(SumoRound::Drawn { round }) => {
+                    layer.send_chat_message(format!("Nobody is left, round {round} is a draw"));
+                    true
+                }
+                Some(SumoRound::Decided) => false,
+                None if game.rules.endless => !time_is_up && !sides.is_empty(),
+                None => !time_is_up && sides.len() > 1,
+            };
+            if keep_running {
+                MatchPhase::Running {
+                    elapsed: elapsed + 1,
+                }
+            } else {
+                let (message, winners) = match winner {
+                    Some((_, _, Some(team))) => (
+                        team.text().bold() + " won the round!",
+                        participants
+                            .iter()
+                            .filter(|(_, t)| *t == Some(*team))
+                            .map(|(e, _)| *e)
+                            .collect(),
+                    ),
+                    Some((e, winner, None)) => {
+                        (winner.0.clone().bold() + " won the round!", vec![*e])
+                    }
+                    None if time_is_up => ("Time is up, nobody won the round".into_text(), vec![]),
+                    None => ("Nobody won the round".into_text(), vec![]),
+                };
+                layer.send_chat_message(message);
+                ended.send(MatchEndedEvent {
+                    arena: arena_id,
+                    winners,
+                    participants: participants.into_iter().map(|(e, _)| e).collect(),
+                    survivors: players.iter().map(|(e, _, _)| *e).collect(),
+                    elapsed,
+                });
+                MatchPhase::Ending {
+                    timer: game.rules.ending_ticks(),
+                }
+            }
+        }
+        MatchPhase::Ending { timer } => {
+            if timer <= 0 {
+                for (e, _) in participants {
+                    commands.entity(e).insert(ReturnToLobby);
+                }
+                commands.entity(arena_id).insert(RestoreArena::default());
+                game.forced = false;
+                MatchPhase::Waiting
+            } else {
+                MatchPhase::Ending { timer: timer - 1 }
+            }
+        }
+    };
+}
+
+#[derive(Component)]
+pub struct ArenaBounds {
+    pub area: Area,
+    pub elimination_y: f64,
+    pub projectiles: Area,
+}
+
+impl ArenaBounds {
+    pub fn contains(&self, pos: DVec3) -> bool {
+        pos.y >= self.elimination_y && self.area.contains(pos)
+    }
+}
+
+#[derive(Component)]
+pub struct Spectator;
+
+// Spectator who came to watch with /spectate, not an eliminated player
+#[derive(Component)]
+pub struct Watcher;
+
+pub fn eliminate_players(
+    server: Res<Server>,
+    mut clients: Query<
+        (
+            Entity,
+            &mut GameMode,
+            &Position,
+            &Username,
+            &EntityLayerId,
+            &CombatState,
+            &PlayerClass,
+        ),
+        With<ArenaPlayer>,
+    >,
+    mut arenas: Query<(&ArenaBounds, &Match, &mut ChunkLayer), With<ArenaLayer>>,
+    mut eliminated: EventWriter<PlayerEliminatedEvent>,
+    mut commands: Commands,
+) {
+    for (e, mut game_mode, pos, username, layer_id, combat, class) in clients.iter_mut() {
+        let Ok((bounds, game, mut layer)) = arenas.get_mut(layer_id.0) else {
+            continue;
+        };
+        if !game.is_running() || bounds.contains(pos.0) {
+            continue;
+        }
+        *game_mode = GameMode::Spectator;
+        commands
+            .entity(e)
+            .remove::<(ArenaPlayer, ClassComponents)>()
+            .insert(Spectator);
+        layer.send_chat_message(username.0.clone().bold() + " was eliminated!");
+        let survived_ticks = match game.phase {
+            MatchPhase::Running { elapsed } => elapsed,
+            _ => 0,
+        };
+        eliminated.send(PlayerEliminatedEvent {
+            arena: layer_id.0,
+            player: e,
+            attacker: combat.recent_attacker(server.current_tick()),
+            class: class.0,
+            survived_ticks,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_match(endless: bool) -> Match {
+        let rules = format!(
+            "min_players = 1\nmax_players = 8\ncountdown_seconds = 10\nending_seconds = 5\nendless = {endless}"
+        );
+        Match::new(toml::from_str(&rules).unwrap())
+    }
+
+    #[test]
+    fn joinable_before_the_round() {
+        for endless in [false, true] {
+            let mut game = new_match(endless);
+            assert!(game.is_joinable());
+            game.phase = MatchPhase::Countdown { timer: 20 };
+            assert!(game.is_joinable());
+            game.phase = MatchPhase::Ending { timer: 20 };
+            assert!(!game.is_joinable());
+        }
+    }
+
+    #[test]
+    fn endless_matches_are_joinable_while_running() {
+        let mut game = new_match(false);
+        game.phase = MatchPhase::Running { elapsed: 100 };
+        assert!(!game.is_joinable());
+
+        let mut practice = new_match(true);
+        practice.phase = MatchPhase::Running { elapsed: 100 };
+        assert!(practice.is_joinable());
+        assert!(!practice.is_starting());
+    }
+
+    #[test]
+    fn leaving_only_counts_in_running_matches() {
+        let arena = Entity::from_raw(1);
+        let player = Entity::from_raw(2);
+        let mut game = new_match(false);
+        assert!(leave_match(&game, arena, player, None, None, 0).is_none());
+
+        game.phase = MatchPhase::Running { elapsed: 100 };
+        let (left, eliminated) = leave_match(&game, arena, player, None, None, 0).unwrap();
+        assert_eq!((left.arena, left.player), (arena, player));
+        // spectators were already eliminated
+        assert!(eliminated.is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    classes::{ClassRegistry, Cooldown, PlayerClass},
+    game::{Match, MatchPhase},
+    level::{ArenaLayer, ArenaPlayer},
+    scoreboard::format_time,
+};
+use valence::{
+    boss_bar::{
+        BossBarBundle, BossBarColor, BossBarDivision, BossBarHealth, BossBarStyle, BossBarTitle,
+    },
+    prelude::*,
+    title::SetTitle,
+    DEFAULT_TPS,
+};
+
+// Shown once in the middle of the screen, removed when sent
+#[derive(Component, Clone)]
+pub struct TitleMessage {
+    pub title: Text,
+    pub subtitle: Text,
+    pub stay_ticks: i32,
+}
+
+impl TitleMessage {
+    pub fn new(title: impl IntoText<'static>, subtitle: impl IntoText<'static>) -> Self {
+        Self {
+            title: title.into_text(),
+            subtitle: subtitle.into_text(),
+            stay_ticks: DEFAULT_TPS.get() as i32,
+        }
+    }
+}
+
+// Shown above the hotbar, removed when sent
+#[derive(Component, Clone)]
+pub struct ActionBarMessage(pub Text);
+
+// Boss bar for everyone in the arena, removing it hides the bar
+#[derive(Component, Clone)]
+pub struct BossBarMessage {
+    pub title: Text,
+    // From 0 to 1
+    pub progress: f32,
+    pub color: BossBarColor,
+}
+
+// Boss bar entity that shows the arena's BossBarMessage
+#[derive(Component)]
+pub struct ArenaBossBar(Entity);
+
+pub fn send_titles(
+    mut clients: Query<(Entity, &mut Client, &TitleMessage)>,
+    mut commands: Commands,
+) {
+    for (e, mut client, message) in clients.iter_mut() {
+        client.set_title_times(2, message.stay_ticks, 5);
+        client.set_subtitle(message.subtitle.clone());
+        client.set_title(message.title.clone());
+        commands.entity(e).remove::<TitleMessage>();
+    }
+}
+
+pub fn send_action_bars(
+    mut clients: Query<(Entity, &mut Client, &ActionBarMessage)>,
+    mut commands: Commands,
+) {
+    for (e, mut client, message) in clients.iter_mut() {
+        client.set_action_bar(message.0.clone());
+        commands.entity(e).remove::<ActionBarMessage>();
+    }
+}
+
+pub fn update_boss_bars(
+    arenas: Query<(Entity, Option<Ref<BossBarMessage>>, Option<&ArenaBossBar>), With<ArenaLayer>>,
+    mut bars: Query<(&mut BossBarTitle, &mut BossBarHealth, &mut BossBarStyle)>,
+    mut commands: Commands,
+) {
+    for (arena, message, bar) in arenas.iter() {
+        match (message, bar) {
+            (Some(message), None) => {
+                let bar = commands
+                    .spawn(BossBarBundle {
+                        title: BossBarTitle(message.title.clone()),
+                        health: BossBarHealth(message.progress),
+                        style: BossBarStyle {
+                            color: message.color,
+                            division: BossBarDivision::NoDivision,
+                        },
+                        layer: EntityLayerId(arena),
+                        ..Default::default()
+                    })
+                    .id();
+                commands.entity(arena).insert(ArenaBossBar(bar));
+            }
+            (Some(message), Some(bar)) => {
+                if !message.is_changed() {
+                    continue;
+                }
+                let Ok((mut title, mut health, mut style)) = bars.get_mut(bar.0) else {
+                    continue;
+                };
+                title.0 = message.title.clone();
+                health.0 = message.progress;
+                style.color = message.color;
+            }
+            (None, Some(bar)) => {
+                commands.entity(bar.0).insert(Despawned);
+                commands.entity(arena).remove::<ArenaBossBar>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+// Countdown titles and round timer of the default match flow
+pub fn match_hud(
+    arenas: Query<(Entity, &Match), (With<ArenaLayer>, Changed<Match>)>,
+    players: Query<(Entity, &EntityLayerId), With<ArenaPlayer>>,
+    mut commands: Commands,
+) {
+    let tps = DEFAULT_TPS.get() as i64;
+    for (arena, game) in arenas.iter() {
+        let title = match game.phase {
+            MatchPhase::Countdown { timer } if timer > 0 && timer % tps == 0 => Some(
+                TitleMessage::new((timer / tps).to_string().bold(), "Get ready"),
+            ),
+            MatchPhase::Running { elapsed: 0 } => Some(TitleMessage::new(
+                "Go!".bold().color(Color::GREEN),
+                "Don't fall",
+            )),
+            _ => None,
+        };
+        if let Some(title) = title {
+            for (player, _) in players.iter().filter(|(_, layer)| layer.0 == arena) {
+                commands.entity(player).insert(title.clone());
+            }
+        }
+
+        match game.phase {
+            // block party shows the round timer instead
+            MatchPhase::Running { .. } if game.rules.block_party.is_some() => {}
+            MatchPhase::Running { elapsed } => {
+                let message = match game.rules.round_ticks() {
+                    Some(round) => BossBarMessage {
+                        title: format!("Time left {}", format_time(round - elapsed)).into_text(),
+                        progress: (1.0 - elapsed as f32 / round as f32).clamp(0.0, 1.0),
+                        color: BossBarColor::Yellow,
+                    },
+                    None => BossBarMessage {
+                        title: format!("Time {}", format_time(elapsed)).into_text(),
+                        progress: 1.0,
+                        color: BossBarColor::Green,
+                    },
+                };
+                // only once a second, so the bar isn't resent every tick
+                if elapsed % tps == 0 {
+                    commands.entity(arena).insert(message);
+                }
+            }
+            _ => {
+                commands.entity(arena).remove::<BossBarMessage>();
+            }
+        }
+    }
+}
+
+// Tenths of a second last shown in the cooldown action bar
+#[derive(Component)]
+pub struct ShownCooldown(i32);
+
+// Shows how long until the class ability can be used again,
+// only sent when the shown value changes
+pub fn cooldown_action_bar(
+    mut clients: Query<(Entity, &Cooldown, &PlayerClass, Option<&mut ShownCooldown>)>,
+    finished: Query<(Entity, Option<&PlayerClass>), (With<ShownCooldown>, Without<Cooldown>)>,
+    registry: Res<ClassRegistry>,
+    mut commands: Commands,
+) {
+    for (e, cooldown, class, shown) in clients.iter_mut() {
+        // very short cooldowns would only flicker
+        if cooldown.0 <= 1 {
+            continue;
+        }
+        let tenths = (cooldown.0 as f32 * 10.0 / DEFAULT_TPS.get() as f32).round() as i32;
+        match shown {
+            Some(shown) if shown.0 == tenths => continue,
+            Some(mut shown) => shown.0 = tenths,
+            None => {
+                commands.entity(e).insert(ShownCooldown(tenths));
+            }
+        }
+        let seconds = tenths as f32 / 10.0;
+        let name = &registry.get(class.0).name;
+        commands.entity(e).insert(ActionBarMessage(
+            format!("{name} ability ready in {seconds:.1}s").into_text(),
+        ));
+    }
+    for (e, class) in finished.iter() {
+        commands.entity(e).remove::<ShownCooldown>();
+        // class is gone if they left the arena
+        if let Some(class) = class {
+            let name = &registry.get(class.0).name;
+            commands.entity(e).insert(ActionBarMessage(
+                format!("{name} ability ready").color(Color::GREEN),
+            ));
+        }
+    }
+}
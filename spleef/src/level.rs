@@ -1,18 +1,24 @@
 use crate::{
     area::Area,
-    classes::{
-        ArcherClass, ClassName, CombatState, GameClass, MageClass, RogueClass, WarriorClass,
-    },
+    classes::{self, ClassComponents, ClassId, ClassName, ClassRegistry, CombatState, PlayerClass},
+    config::{Config, ConfigError},
+    damage::{BlockDamageEvent, DamageCause},
+    dynamic_blocks::{self, DynamicBlocks},
+    game::{Match, Spectator, Watcher},
+    map::{BlockPalette, Maps, SpawnPoints, TriggerRegion},
+    splegg::{EggLauncher, Splegg},
+    teams::Team,
+    tnt_run::TntRun,
 };
-use std::{collections::HashMap, marker::PhantomData, path::PathBuf, str::FromStr};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
 use valence::{
-    advancement::bevy_hierarchy::{BuildChildren, Parent},
-    anvil::parsing::{DimensionFolder, ParseChunkError},
-    entity::{text_display::TextDisplayEntityBundle, OnGround},
-    nbt::value::ValueRef,
+    anvil::parsing::DimensionFolder,
+    entity::{attributes::EntityAttributes, OnGround},
+    layer::chunk::UnloadedChunk,
     prelude::*,
     protocol::{packets::play::BlockBreakingProgressS2c, WritePacket},
-    text::TextContent,
+    registry::{biome::Biome, RegistryCodec},
     DEFAULT_TPS,
 };
 
@@ -35,21 +41,65 @@ pub const WOOL: [BlockState; 16] = [
     BlockState::BLACK_WOOL,
 ];
 
-pub fn load_level(
-    path: impl Into<PathBuf>,
-    biomes: &BiomeRegistry,
-    dimensions: &DimensionTypeRegistry,
-    server: &Server,
-    area: &Area,
-) -> Result<LayerBundle, ParseChunkError> {
-    let mut folder = DimensionFolder::new(path, &biomes);
-    let mut layer = LayerBundle::new(ident!("overworld"), dimensions, biomes, server);
-    for pos in area.iter_chunk_pos() {
-        if let Some(chunk) = folder.get_chunk(pos)? {
-            layer.chunk.insert_chunk(pos, chunk.chunk);
+// The biomes valence registers at startup, in the same order, so maps can be
+// parsed before the app runs
+pub fn vanilla_biomes() -> BiomeRegistry {
+    let codec = RegistryCodec::default();
+    let mut biomes = BiomeRegistry::default();
+    for value in codec.registry(BiomeRegistry::KEY) {
+        let biome = Biome::deserialize(value.element.clone()).expect("vanilla biome is invalid");
+        biomes.insert(value.name.clone(), biome);
+    }
+    biomes
+}
+
+// Chunks of a map read from disk, every arena instance gets its own copy
+pub struct MapChunks(Vec<(ChunkPos, UnloadedChunk)>);
+
+impl MapChunks {
+    pub fn load(dir: &Path, biomes: &BiomeRegistry, area: &Area) -> Result<Self, ConfigError> {
+        let mut folder = DimensionFolder::new(dir, biomes);
+        let mut chunks = vec![];
+        for pos in area.iter_chunk_pos() {
+            let chunk = folder
+                .get_chunk(pos)
+                .map_err(|e| ConfigError::Map(dir.to_owned(), pos, e))?;
+            if let Some(chunk) = chunk {
+                chunks.push((pos, chunk.chunk));
+            }
         }
+        Ok(Self(chunks))
+    }
+
+    pub fn to_layer(
+        &self,
+        biomes: &BiomeRegistry,
+        dimensions: &DimensionTypeRegistry,
+        server: &Server,
+    ) -> LayerBundle {
+        let mut layer = LayerBundle::new(ident!("overworld"), dimensions, biomes, server);
+        for (pos, chunk) in &self.0 {
+            layer.chunk.insert_chunk(*pos, chunk.clone());
+        }
+        layer
+    }
+}
+
+// Chunks of every map from the config, loaded before the server starts
+#[derive(Resource)]
+pub struct Levels {
+    pub lobby: MapChunks,
+    pub arena: MapChunks,
+}
+
+impl Levels {
+    pub fn load(config: &Config, maps: &Maps) -> Result<Self, ConfigError> {
+        let biomes = vanilla_biomes();
+        Ok(Self {
+            lobby: MapChunks::load(&config.lobby.map, &biomes, &maps.lobby.area)?,
+            arena: MapChunks::load(&config.arena.map.map, &biomes, &maps.arena.area)?,
+        })
     }
-    Ok(layer)
 }
 
 #[derive(Component)]
@@ -61,134 +111,64 @@ pub struct LobbyPlayer;
 #[derive(Component)]
 pub struct ArenaLayer;
 
+// Number of the arena instance shown to players, starts from 1
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct ArenaNumber(pub i32);
+
 #[derive(Component)]
 pub struct ArenaPlayer;
 
-pub fn extract_text_from_sign_nbt(nbt: &Compound) -> Option<Text> {
-    let text = nbt.get("front_text").map(|x| x.as_value_ref())?;
-    let ValueRef::Compound(text) = text else {
-        return None;
-    };
-    let text = text.get("messages").map(|x| x.as_value_ref())?;
-    let ValueRef::List(text) = text else {
-        return None;
-    };
-    let text = text.get(0)?;
-    let ValueRef::String(text) = text else {
-        return None;
-    };
-    let text = Text::from_str(text).ok()?;
-    Some(text)
+#[derive(Component)]
+pub struct ClassTrigger {
+    pub class: ClassId,
+    pub area: Area,
 }
 
-pub fn create_class_text(
-    layer_id: Entity,
-    layer: &mut LayerBundle,
-    area: &Area,
-    commands: &mut Commands,
-) {
-    const SIGNS: [BlockState; 4] = [
-        BlockState::OAK_SIGN,
-        BlockState::OAK_WALL_SIGN,
-        BlockState::OAK_HANGING_SIGN,
-        BlockState::OAK_WALL_HANGING_SIGN,
-    ];
+fn trigger_area(layer: &LayerBundle, area: &Area, region: &TriggerRegion) -> Option<Area> {
+    let kind = match region {
+        TriggerRegion::Area(trigger) => return Some(*trigger),
+        TriggerRegion::Block { block } => block.0,
+    };
+    let mut trigger: Option<Area> = None;
     for pos in area.iter_block_pos() {
         let Some(block) = layer.chunk.block(pos) else {
             continue;
         };
-        if !SIGNS.contains(&block.state) {
+        if block.state.to_kind() != kind {
             continue;
         }
-        let Some(nbt) = block.nbt else {
-            continue;
-        };
-        let Some(text) = extract_text_from_sign_nbt(nbt) else {
-            continue;
-        };
-        let content = &text.content;
-        let TextContent::Text { text } = content else {
-            continue;
-        };
-        let display_text: Text = match text.as_ref() {
-            "warrior" => "Warrior Class",
-            "archer" => "Archer Class",
-            "mage" => "Mage Class",
-            "rogue" => "Rogue Class",
-            _ => {
-                continue;
-            }
-        }
-        .into_text();
-        layer.chunk.set_block(pos, BlockState::AIR).unwrap();
-        commands.spawn(TextDisplayEntityBundle {
-            layer: EntityLayerId(layer_id),
-            text_display_text: valence::entity::text_display::Text(display_text),
-            position: Position([pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 - 0.5].into()),
-            look: Look::new(180.0, 0.0),
-            ..Default::default()
+        let block_area = Area::new(pos, pos);
+        trigger = Some(match trigger {
+            Some(t) => t.merge(&block_area),
+            None => block_area,
         });
     }
+    trigger.map(|t| t.expand(BlockPos::new(0, 3, 0)))
 }
 
-#[derive(Component)]
-pub struct ClassTrigger<Class> {
-    pub area: Area,
-    _class: PhantomData<Class>,
-}
-
-pub fn create_class_trigger(layer: &mut LayerBundle, area: &Area, commands: &mut Commands) {
-    let mut triggers: HashMap<BlockState, Option<Area>> = HashMap::from([
-        (BlockState::LIGHT_GRAY_WOOL, None),
-        (BlockState::ORANGE_WOOL, None),
-        (BlockState::RED_WOOL, None),
-        (BlockState::LIGHT_BLUE_WOOL, None),
-    ]);
-    for pos in area.iter_block_pos() {
-        let Some(block) = layer.chunk.block(pos) else {
+pub fn create_class_trigger(
+    layer: &LayerBundle,
+    area: &Area,
+    triggers: &HashMap<String, TriggerRegion>,
+    registry: &ClassRegistry,
+    commands: &mut Commands,
+) {
+    for (class, def) in registry.iter() {
+        let Some(region) = triggers.get(&def.id) else {
             continue;
         };
-        let Some(trigger) = triggers.get_mut(&block.state) else {
+        let Some(area) = trigger_area(layer, area, region) else {
             continue;
         };
-        let block_area = Area::new(pos, pos);
-        let new_trigger = match trigger {
-            Some(t) => t.merge(&block_area),
-            None => block_area,
-        };
-        *trigger = Some(new_trigger);
-    }
-    let mut triggers: HashMap<_, _> = triggers.into_iter().map(|(k, v)| (k, v.unwrap())).collect();
-    for (_, area) in triggers.iter_mut() {
-        *area = area.expand(BlockPos::new(0, 3, 0));
+        commands.spawn(ClassTrigger { class, area });
     }
-    commands.spawn(ClassTrigger::<WarriorClass> {
-        area: triggers.remove(&BlockState::LIGHT_GRAY_WOOL).unwrap(),
-        _class: PhantomData,
-    });
-    commands.spawn(ClassTrigger::<ArcherClass> {
-        area: triggers.remove(&BlockState::ORANGE_WOOL).unwrap(),
-        _class: PhantomData,
-    });
-    commands.spawn(ClassTrigger::<MageClass> {
-        area: triggers.remove(&BlockState::RED_WOOL).unwrap(),
-        _class: PhantomData,
-    });
-    commands.spawn(ClassTrigger::<RogueClass> {
-        area: triggers.remove(&BlockState::LIGHT_BLUE_WOOL).unwrap(),
-        _class: PhantomData,
-    });
-}
-
-#[derive(Component, Default)]
-pub struct DynamicBlocks {
-    pub data: HashMap<BlockPos, Entity>,
 }
 
 pub fn create_arena_blocks(
     layer_id: Entity,
     layer: &mut LayerBundle,
     area: &Area,
+    palette: &BlockPalette,
     commands: &mut Commands,
 ) {
     let blocks = area.iter_block_pos();
@@ -197,34 +177,161 @@ pub fn create_arena_blocks(
         let Some(block) = layer.chunk.block(pos) else {
             continue;
         };
-        if !WOOL.contains(&block.state) {
+        let Some(durability) = palette.durability(block.state) else {
             continue;
+        };
+        dynamic.insert(pos, durability);
+    }
+    let snapshot = ArenaSnapshot::capture(&layer.chunk, area);
+    commands.entity(layer_id).insert((dynamic, snapshot));
+}
+
+#[derive(Component)]
+pub struct ArenaSnapshot {
+    area: Area,
+    // Block states in Area::iter_block_pos order
+    blocks: Vec<BlockState>,
+}
+
+impl ArenaSnapshot {
+    pub fn capture(layer: &ChunkLayer, area: &Area) -> Self {
+        let blocks = area
+            .iter_block_pos()
+            .map(|pos| layer.block(pos).map_or(BlockState::AIR, |b| b.state))
+            .collect();
+        Self {
+            area: *area,
+            blocks,
         }
+    }
 
-        let block = commands
-            .spawn((BreakingState::default(), BlockPosition { pos }))
-            .id();
-        dynamic.data.insert(pos, block);
-        commands.entity(layer_id).add_child(block);
+    pub fn area(&self) -> &Area {
+        &self.area
+    }
+
+    pub fn block(&self, pos: BlockPos) -> Option<BlockState> {
+        self.area.index_of(pos).map(|i| self.blocks[i])
+    }
+}
+
+// Insert into arena layer to restore it from ArenaSnapshot
+// Removed when restoration is finished
+#[derive(Component, Default)]
+pub struct RestoreArena {
+    // Offset of next x slice from area min
+    progress: i32,
+}
+
+impl RestoreArena {
+    // Restoring the whole arena in one tick causes a lag spike, so do it in slices
+    const SLICES_PER_TICK: i32 = 8;
+}
+
+pub fn restore_arena(
+    mut layers: Query<(
+        Entity,
+        &mut ChunkLayer,
+        &mut DynamicBlocks,
+        &ArenaSnapshot,
+        &BlockPalette,
+        &mut RestoreArena,
+        Option<&mut TntRun>,
+    )>,
+    mut commands: Commands,
+) {
+    for (layer_id, mut layer, mut dynamic, snapshot, palette, mut restore, tnt) in layers.iter_mut()
+    {
+        if let Some(mut tnt) = tnt {
+            tnt.reset();
+        }
+        let min = snapshot.area().min();
+        let max = snapshot.area().max();
+        let from = min.x + restore.progress;
+        let to = (from + RestoreArena::SLICES_PER_TICK - 1).min(max.x);
+        let slice = Area::new([from, min.y, min.z], [to, max.y, max.z]);
+        for pos in slice.iter_block_pos() {
+            let Some(state) = snapshot.block(pos) else {
+                continue;
+            };
+            if layer.block(pos).map(|b| b.state) != Some(state) {
+                layer.set_block(pos, state);
+            }
+            let Some(durability) = palette.durability(state) else {
+                continue;
+            };
+            dynamic.insert(pos, durability);
+        }
+        restore.progress = to - min.x + 1;
+        if to >= max.x {
+            commands.entity(layer_id).remove::<RestoreArena>();
+        }
     }
-    commands.entity(layer_id).insert(dynamic);
 }
 
-pub fn do_class_triggers<Class: Component + GameClass>(
+// Arena instance the player is moving to
+#[derive(Component)]
+pub struct JoinArena(pub Entity);
+
+// Counts players in every arena instance to route newcomers to one with space
+pub struct ArenaRouter {
+    occupancy: HashMap<Entity, usize>,
+}
+
+impl ArenaRouter {
+    pub fn new<'a>(players: impl Iterator<Item = &'a EntityLayerId>) -> Self {
+        let mut occupancy: HashMap<Entity, usize> = HashMap::new();
+        for layer in players {
+            *occupancy.entry(layer.0).or_default() += 1;
+        }
+        Self { occupancy }
+    }
+
+    pub fn pick<'a>(
+        &mut self,
+        arenas: impl Iterator<Item = (Entity, &'a Match)>,
+    ) -> Option<Entity> {
+        // fill the fullest arena first, so matches start sooner
+        let arena = arenas
+            .map(|(id, game)| (id, game, self.occupancy.get(&id).copied().unwrap_or(0)))
+            .filter(|(_, game, count)| game.is_joinable() && *count < game.rules.max_players)
+            .max_by_key(|(_, _, count)| *count)
+            .map(|(id, _, _)| id)?;
+        *self.occupancy.entry(arena).or_default() += 1;
+        Some(arena)
+    }
+}
+
+// Components of a lobby player who picked a class and is moving to the arena
+pub fn arena_player_bundle(arena: Entity, class: ClassId, registry: &ClassRegistry) -> impl Bundle {
+    (
+        ArenaPlayer,
+        JoinArena(arena),
+        PlayerClass(class),
+        ClassName(registry.get(class).name.clone()),
+        CombatState::default(),
+    )
+}
+
+pub fn do_class_triggers(
     mut clients: Query<(Entity, &Position), (With<Client>, With<LobbyPlayer>)>,
-    trigger: Query<&ClassTrigger<Class>>,
+    triggers: Query<&ClassTrigger>,
+    arenas: Query<(Entity, &Match), With<ArenaLayer>>,
+    players: Query<&EntityLayerId, With<ArenaPlayer>>,
+    registry: Res<ClassRegistry>,
     mut commands: Commands,
 ) {
-    let trigger = trigger.single();
+    let mut router = ArenaRouter::new(players.iter());
     for (e, pos) in clients.iter_mut() {
-        if trigger.area.contains(pos.0) {
-            commands.entity(e).remove::<LobbyPlayer>().insert((
-                ArenaPlayer,
-                Class::default(),
-                ClassName(Class::name()),
-                CombatState::default(),
-            ));
-        }
+        let Some(trigger) = triggers.iter().find(|t| t.area.contains(pos.0)) else {
+            continue;
+        };
+        let Some(arena) = router.pick(arenas.iter()) else {
+            continue;
+        };
+        commands
+            .entity(e)
+            .remove::<LobbyPlayer>()
+            .insert(arena_player_bundle(arena, trigger.class, &registry));
     }
 }
 
@@ -238,13 +345,13 @@ pub fn move_to_arena(
             &mut VisibleEntityLayers,
             &mut Position,
             &ClassName,
+            &JoinArena,
         ),
         Added<ArenaPlayer>,
     >,
-    arena: Query<Entity, (With<ChunkLayer>, With<EntityLayer>, With<ArenaLayer>)>,
+    mut arenas: Query<&mut SpawnPoints, With<ArenaLayer>>,
     mut commands: Commands,
 ) {
-    let arena = arena.single();
     for (
         e,
         mut client,
@@ -253,18 +360,92 @@ pub fn move_to_arena(
         mut visible_entity_layers,
         mut pos,
         class_name,
+        join,
     ) in clients.iter_mut()
     {
+        let arena = join.0;
+        let Ok(mut spawns) = arenas.get_mut(arena) else {
+            continue;
+        };
         entity_layer.0 = arena;
         visible_chunk_layer.0 = arena;
         visible_entity_layers.0.clear();
         visible_entity_layers.0.insert(arena);
 
-        pos.set([0.0, 61.0, 0.0]);
+        pos.set(spawns.next());
         commands
             .entity(e)
+            .remove::<JoinArena>()
             .insert((ChunksLoading::default(), KeepPosition(pos.0)));
-        client.send_chat_message("You've picked ".into_text() + class_name.0.bold() + " class!");
+        client.send_chat_message(
+            "You've picked ".into_text() + class_name.0.clone().bold() + " class!",
+        );
+    }
+}
+
+// Insert into a player to send them back to the lobby
+#[derive(Component)]
+pub struct ReturnToLobby;
+
+pub fn move_to_lobby(
+    mut clients: Query<
+        (
+            Entity,
+            &mut EntityLayerId,
+            &mut VisibleChunkLayer,
+            &mut VisibleEntityLayers,
+            &mut Position,
+            &mut GameMode,
+            &mut Inventory,
+            &mut EntityAttributes,
+        ),
+        With<ReturnToLobby>,
+    >,
+    mut tnt_runs: Query<&mut TntRun>,
+    mut lobby: Query<
+        (Entity, &mut SpawnPoints),
+        (With<ChunkLayer>, With<EntityLayer>, With<LobbyLayer>),
+    >,
+    mut commands: Commands,
+) {
+    let (lobby, mut spawns) = lobby.single_mut();
+    for (
+        e,
+        mut entity_layer,
+        mut visible_chunk_layer,
+        mut visible_entity_layers,
+        mut pos,
+        mut game_mode,
+        mut inv,
+        mut attr,
+    ) in clients.iter_mut()
+    {
+        if let Ok(mut tnt) = tnt_runs.get_mut(entity_layer.0) {
+            tnt.forget(e);
+        }
+        entity_layer.0 = lobby;
+        visible_chunk_layer.0 = lobby;
+        visible_entity_layers.0.clear();
+        visible_entity_layers.0.insert(lobby);
+
+        pos.set(spawns.next());
+        *game_mode = GameMode::Adventure;
+        // also takes the splegg launcher and ammo
+        classes::clear_inventory(inv.as_mut());
+        classes::reset_attributes(attr.as_mut());
+
+        commands
+            .entity(e)
+            .remove::<(
+                ReturnToLobby,
+                ArenaPlayer,
+                Spectator,
+                Watcher,
+                Team,
+                EggLauncher,
+                ClassComponents,
+            )>()
+            .insert((LobbyPlayer, ChunksLoading::default(), KeepPosition(pos.0)));
     }
 }
 
@@ -312,95 +493,68 @@ pub fn update_inventory_while_chunks_loading(
     }
 }
 
-#[derive(Component)]
-pub struct BreakingState {
-    pub hp: i32,
-}
-
-impl BreakingState {
-    const MAX_HP: i32 = DEFAULT_TPS.get() as i32 * 5;
-
-    pub fn destroy_stage(&self) -> u8 {
-        let stage = (Self::MAX_HP - self.hp.clamp(0, Self::MAX_HP)) * 11 / Self::MAX_HP;
-        if stage == 0 {
-            10
-        } else {
-            stage as u8 - 1
-        }
-    }
-}
-
-impl Default for BreakingState {
-    fn default() -> Self {
-        Self { hp: Self::MAX_HP }
-    }
-}
-
-#[derive(Component)]
-pub struct BlockPosition {
-    pub pos: BlockPos,
+// Dynamic block the player stands on, the closest one if they stand on an edge
+pub fn block_under_player(pos: DVec3, dynamic: &DynamicBlocks) -> Option<BlockPos> {
+    let mut potential_blocks: Vec<_> = [-0.5, 0.0, 0.5]
+        .into_iter()
+        .flat_map(|x| [-0.5, 0.0, 0.5].map(|z| (x, z)))
+        .map(|(x, z)| pos + DVec3::new(x, -0.5, z))
+        .collect();
+    potential_blocks.sort_by(|a, b| pos.distance(*a).total_cmp(&pos.distance(*b)));
+    potential_blocks.dedup();
+    potential_blocks
+        .into_iter()
+        .map(BlockPos::from)
+        .find(|block_pos| dynamic.contains(*block_pos))
 }
 
 pub fn break_blocks_under_player(
-    clients: Query<(&Position, &OnGround), With<ArenaPlayer>>,
-    arena: Query<&DynamicBlocks, With<ArenaLayer>>,
-    mut blocks: Query<&mut BreakingState>,
+    clients: Query<(Entity, &Position, &OnGround, &EntityLayerId), With<ArenaPlayer>>,
+    arenas: Query<(&DynamicBlocks, &Match), (With<ArenaLayer>, Without<TntRun>, Without<Splegg>)>,
+    mut damage: EventWriter<BlockDamageEvent>,
 ) {
-    let arena = arena.single();
-    for (pos, ground) in clients.iter() {
+    for (player, pos, ground, layer) in clients.iter() {
         if !ground.0 {
             continue;
         }
-        let mut potential_blocks: Vec<_> = [-0.5, 0.0, 0.5]
-            .into_iter()
-            .flat_map(|x| [-0.5, 0.0, 0.5].map(|z| (x, z)))
-            .map(|(x, z)| pos.0 + DVec3::new(x, -0.5, z))
-            .collect();
-        potential_blocks.sort_by(|a, b| pos.0.distance(*a).total_cmp(&pos.0.distance(*b)));
-        potential_blocks.dedup();
-        for pos in potential_blocks {
-            let block_pos: BlockPos = pos.into();
-            let Some(e) = arena.data.get(&block_pos) else {
-                continue;
-            };
-            let Ok(mut state) = blocks.get_mut(*e) else {
-                continue;
-            };
-            state.hp -= 1;
-            break;
-        }
-    }
-}
-
-pub fn destroy_broken_blocks(
-    mut layers: Query<(&mut ChunkLayer, &mut DynamicBlocks)>,
-    states: Query<(Entity, &Parent, &BlockPosition, &BreakingState), Changed<BreakingState>>,
-    mut commands: Commands,
-) {
-    for (e, parent, block, state) in states.iter() {
-        if state.hp > 0 {
+        let Ok((arena, game)) = arenas.get(layer.0) else {
+            continue;
+        };
+        if !game.is_running() {
             continue;
         }
-        if let Ok((mut layer, mut dynamic)) = layers.get_mut(parent.get()) {
-            layer.set_block(block.pos, BlockState::AIR);
-            dynamic.data.remove(&block.pos);
+        if let Some(block_pos) = block_under_player(pos.0, arena) {
+            damage.send(BlockDamageEvent {
+                layer: layer.0,
+                pos: block_pos,
+                amount: 1,
+                source: player,
+                cause: DamageCause::Standing,
+            });
         }
-        commands.entity(e).despawn();
     }
 }
 
 pub fn send_breaking_state(
-    mut layers: Query<&mut ChunkLayer>,
-    states: Query<(Entity, &Parent, &BlockPosition, &BreakingState), Changed<BreakingState>>,
+    mut layers: Query<(&mut ChunkLayer, &mut DynamicBlocks, &BlockPalette)>,
 ) {
-    for (e, parent, block, state) in states.iter() {
-        let Ok(mut layer) = layers.get_mut(parent.get()) else {
-            continue;
-        };
-        layer.write_packet(&BlockBreakingProgressS2c {
-            entity_id: (e.index() as i32).into(),
-            position: block.pos,
-            destroy_stage: state.destroy_stage(),
-        });
+    for (mut layer, mut dynamic, palette) in layers.iter_mut() {
+        let dirty: Vec<_> = dynamic.drain_dirty().collect();
+        for (pos, hp) in dirty {
+            let durability = layer
+                .block(pos)
+                .and_then(|block| palette.durability(block.state))
+                .unwrap_or(BlockPalette::DEFAULT_DURABILITY);
+            let destroy_stage = dynamic_blocks::destroy_stage(hp, durability);
+            let entity_id = dynamic.breaker_id(pos);
+            if destroy_stage >= 10 {
+                dynamic.release_breaker(pos);
+            }
+            layer.write_packet(&BlockBreakingProgressS2c {
+                entity_id: entity_id.into(),
+                position: pos,
+                destroy_stage,
+            });
+        }
     }
 }
@@ -1,19 +1,88 @@
-use area::Area;
-use classes::{ArcherClass, MageClass, RogueClass, WarriorClass};
-use level::{ArenaLayer, LobbyLayer, LobbyPlayer};
-use valence::{prelude::*, spawn::IsFlat};
+use block_party::BlockParty;
+use classes::ClassRegistry;
+use config::Config;
+use damage::{BlockDamageEvent, BlockDestroyedEvent};
+use game::{ArenaBounds, Match, MatchEndedEvent, PlayerEliminatedEvent, PlayerLeftMatchEvent};
+use level::{ArenaLayer, ArenaNumber, Levels, LobbyLayer, LobbyPlayer, MapChunks};
+use map::{BlockPalette, Maps, SpawnPoints};
+use markers::MapMarkers;
+use regeneration::Regeneration;
+use splegg::Splegg;
+use stats::{StatsCommand, StatsStore};
+use std::fmt::Display;
+use sumo::Sumo;
+use tnt_run::TntRun;
+use valence::{
+    command::{scopes::CommandScopes, AddCommand, CommandScopeRegistry},
+    prelude::*,
+    spawn::IsFlat,
+};
 
 pub mod area;
+mod block_party;
 mod classes;
+mod commands;
+mod config;
+mod damage;
+mod dynamic_blocks;
+mod effects;
+mod game;
+mod hud;
 mod level;
+mod map;
+mod markers;
+mod power_ups;
+pub mod raycast;
+mod regeneration;
+mod scoreboard;
+mod splegg;
+mod stats;
+mod sumo;
+mod teams;
+mod tnt_run;
+
+fn exit_on_error<T>(result: Result<T, impl Display>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
 
 pub fn main() {
+    let config = exit_on_error(Config::load(config::CONFIG_PATH));
+    let maps = exit_on_error(Maps::load(&config.lobby.map, &config.arena.map.map));
+    // mode is validated when config is loaded
+    exit_on_error(
+        maps.arena
+            .validate_mode(&config.arena.mode, config.arena_rules().unwrap()),
+    );
+    let levels = exit_on_error(Levels::load(&config, &maps));
+    let registry = exit_on_error(ClassRegistry::load(&config.classes));
+    let stats = exit_on_error(StatsStore::load(&config.stats));
     App::new()
-        .insert_resource(NetworkSettings {
-            connection_mode: ConnectionMode::Offline,
-            ..Default::default()
-        })
+        .insert_resource(config.network_settings())
+        .insert_resource(config)
+        .insert_resource(maps)
+        .insert_resource(levels)
+        .insert_resource(registry)
+        .insert_resource(stats)
         .add_plugins(DefaultPlugins)
+        .add_event::<BlockDamageEvent>()
+        .add_event::<BlockDestroyedEvent>()
+        .add_event::<MatchEndedEvent>()
+        .add_event::<PlayerEliminatedEvent>()
+        .add_event::<PlayerLeftMatchEvent>()
+        .add_command::<StatsCommand>()
+        .add_command::<commands::LeaveCommand>()
+        .add_command::<commands::ClassCommand>()
+        .add_command::<commands::SpectateCommand>()
+        .add_command::<commands::StartCommand>()
+        .add_command::<commands::StopCommand>()
+        .add_command::<commands::ResetCommand>()
+        .add_command::<commands::ArenaCommand>()
         .add_systems(Startup, setup)
         .add_systems(
             PreUpdate,
@@ -26,31 +95,89 @@ pub fn main() {
             Update,
             (
                 init_clients,
-                level::do_class_triggers::<WarriorClass>,
-                level::do_class_triggers::<ArcherClass>,
-                level::do_class_triggers::<MageClass>,
-                level::do_class_triggers::<RogueClass>,
+                level::do_class_triggers,
                 level::move_to_arena,
+                level::move_to_lobby,
                 level::keep_position_while_chunks_loading,
                 level::update_inventory_while_chunks_loading,
+                scoreboard::update_sidebars,
+                scoreboard::update_tab_groups,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                hud::match_hud,
+                hud::cooldown_action_bar,
+                hud::update_boss_bars,
+                hud::send_titles,
+                hud::send_action_bars,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                game::leave_on_disconnect,
+                // before the player entity is despawned
+                (
+                    stats::record_eliminations,
+                    stats::record_matches,
+                    stats::record_leaves,
+                )
+                    .after(game::leave_on_disconnect),
+                stats::record_blocks,
+                stats::save_stats,
+                stats::handle_stats_command,
+            ),
+        )
+        .add_systems(Update, (teams::assign_teams,))
+        .add_systems(
+            Update,
+            (
+                commands::handle_leave_command,
+                commands::handle_class_command,
+                commands::handle_spectate_command,
+                commands::handle_start_command,
+                commands::handle_stop_command,
+                commands::handle_reset_command,
+                commands::handle_arena_command,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                (sumo::update_sumo_rounds, apply_deferred, game::update_match).chain(),
+                level::restore_arena,
+                game::eliminate_players,
                 (
                     level::break_blocks_under_player,
-                    level::destroy_broken_blocks,
+                    tnt_run::crumble_stepped_blocks,
+                    damage::apply_block_damage,
                 )
                     .chain(),
-                classes::init_warrior,
-                classes::init_archer,
-                classes::init_mage,
-                classes::init_rogue,
-                classes::warrior_dig,
-                classes::archer_shoot,
+                tnt_run::announce_floors,
+                effects::spawn_destroy_effects,
+                effects::update_debris,
+                power_ups::drop_power_ups,
+                power_ups::pick_up_power_ups,
+                regeneration::queue_regeneration,
+                regeneration::regenerate_blocks,
+                block_party::update_block_party,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                classes::init_class,
+                classes::dig_ability,
+                classes::arrow_ability,
+                classes::fireball_ability,
                 classes::combat,
                 (
                     (classes::arrow_intersection, classes::arrow_oob),
                     classes::arrow_movement,
                 )
                     .chain(),
-                classes::mage_shoot,
                 (
                     (classes::fireball_intersection, classes::fireball_oob),
                     classes::fireball_movement,
@@ -58,7 +185,21 @@ pub fn main() {
                     .chain(),
             ),
         )
+        .add_systems(
+            Update,
+            (
+                splegg::give_launchers.after(classes::init_class),
+                splegg::launch_eggs,
+                splegg::refill_at_edge,
+                (
+                    (splegg::egg_intersection, splegg::egg_oob),
+                    splegg::egg_movement,
+                )
+                    .chain(),
+            ),
+        )
         .add_systems(PostUpdate, (level::send_breaking_state,))
+        .add_systems(Last, stats::save_stats_on_exit)
         .run();
 }
 
@@ -67,21 +208,130 @@ fn setup(
     server: Res<Server>,
     dimensions: Res<DimensionTypeRegistry>,
     biomes: Res<BiomeRegistry>,
+    config: Res<Config>,
+    maps: Res<Maps>,
+    levels: Res<Levels>,
+    registry: Res<ClassRegistry>,
+    mut scopes: ResMut<CommandScopeRegistry>,
 ) {
-    let lobby_area = Area::new([-50, 50, -50], [50, 80, 50]);
-    let mut lobby =
-        level::load_level("maps/lobby", &biomes, &dimensions, &server, &lobby_area).unwrap();
-    let lobby_id = commands.spawn(LobbyLayer).id();
-    level::create_class_text(lobby_id, &mut lobby, &lobby_area, &mut commands);
-    level::create_class_trigger(&mut lobby, &lobby_area, &mut commands);
+    commands::link_scopes(&mut scopes);
+
+    let lobby_meta = &maps.lobby;
+    let mut lobby = levels.lobby.to_layer(&biomes, &dimensions, &server);
+    let markers = MapMarkers::scan(&mut lobby, &lobby_meta.area, &registry);
+    let lobby_id = commands
+        .spawn((
+            LobbyLayer,
+            markers.spawn_points(lobby_meta),
+            markers.regions(),
+        ))
+        .id();
+    markers.spawn_holograms(lobby_id, &mut commands);
+    level::create_class_trigger(
+        &lobby,
+        &lobby_meta.area,
+        &markers.merge_triggers(&lobby_meta.triggers),
+        &registry,
+        &mut commands,
+    );
     commands.entity(lobby_id).insert(lobby);
 
-    let arena_area = Area::new([-100, 50, -100], [100, 100, 100]);
-    let mut arena =
-        level::load_level("maps/arena", &biomes, &dimensions, &server, &arena_area).unwrap();
-    let arena_id = commands.spawn(ArenaLayer).id();
-    level::create_arena_blocks(arena_id, &mut arena, &arena_area, &mut commands);
-    commands.entity(arena_id).insert(arena);
+    for i in 0..config.arena.instances {
+        spawn_arena(
+            ArenaNumber(i as i32 + 1),
+            &levels.arena,
+            &mut commands,
+            &server,
+            &dimensions,
+            &biomes,
+            &config,
+            &maps,
+            &registry,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_arena(
+    number: ArenaNumber,
+    chunks: &MapChunks,
+    commands: &mut Commands,
+    server: &Server,
+    dimensions: &DimensionTypeRegistry,
+    biomes: &BiomeRegistry,
+    config: &Config,
+    maps: &Maps,
+    registry: &ClassRegistry,
+) {
+    let arena_config = &config.arena;
+    let meta = &maps.arena;
+    // validated when config is loaded
+    let rules = config.arena_rules().unwrap().clone();
+    let regeneration = rules.regeneration_ticks().map(Regeneration::new);
+    // validated with the map
+    let block_party = rules
+        .block_party
+        .as_ref()
+        .and_then(|_| meta.floor)
+        .map(BlockParty::new);
+    // block party floor only changes between rounds, sumo has no block breaking
+    let palette = if block_party.is_some() || rules.sumo.is_some() {
+        BlockPalette::default()
+    } else {
+        meta.palette()
+    };
+    // sumo players are out as soon as they fall off the platform
+    let elimination_y = match (&rules.sumo, meta.floor) {
+        (Some(_), Some(floor)) => floor.min().y as f64,
+        _ => meta.elimination_y(),
+    };
+    let sumo = rules.sumo.as_ref().map(|_| Sumo::default());
+    let splegg = rules.splegg.is_some();
+    let tnt_run = rules
+        .tnt_run
+        .as_ref()
+        .map(|_| TntRun::new(meta.floor_heights()));
+    let palette = match &rules.tnt_run {
+        Some(tnt) => palette.with_durability(tnt.delay_ticks),
+        None => palette,
+    };
+    let mut arena = chunks.to_layer(biomes, dimensions, server);
+    let markers = MapMarkers::scan(&mut arena, &meta.area, registry);
+    let arena_id = commands
+        .spawn((
+            ArenaLayer,
+            number,
+            markers.spawn_points(meta),
+            markers.regions(),
+            ArenaBounds {
+                area: markers.arena_area(&meta.area),
+                elimination_y,
+                projectiles: arena_config.projectile_bounds,
+            },
+            Match::new(rules),
+        ))
+        .id();
+    markers.spawn_holograms(arena_id, commands);
+    let sidebar = scoreboard::create_sidebar(arena_id, commands);
+    level::create_arena_blocks(arena_id, &mut arena, &meta.area, &palette, commands);
+    commands
+        .entity(arena_id)
+        .insert((arena, palette, arena_config.effects.clone(), sidebar));
+    if let Some(regeneration) = regeneration {
+        commands.entity(arena_id).insert(regeneration);
+    }
+    if let Some(block_party) = block_party {
+        commands.entity(arena_id).insert(block_party);
+    }
+    if let Some(tnt_run) = tnt_run {
+        commands.entity(arena_id).insert(tnt_run);
+    }
+    if let Some(sumo) = sumo {
+        commands.entity(arena_id).insert(sumo);
+    }
+    if splegg {
+        commands.entity(arena_id).insert(Splegg);
+    }
 }
 
 fn init_clients(
@@ -95,13 +345,19 @@ fn init_clients(
             &mut GameMode,
             &mut Position,
             &mut IsFlat,
+            &mut CommandScopes,
+            &Username,
         ),
         Added<Client>,
     >,
-    lobby: Query<Entity, (With<ChunkLayer>, With<EntityLayer>, With<LobbyLayer>)>,
+    mut lobby: Query<
+        (Entity, &mut SpawnPoints),
+        (With<ChunkLayer>, With<EntityLayer>, With<LobbyLayer>),
+    >,
+    config: Res<Config>,
     mut commands: Commands,
 ) {
-    let lobby = lobby.single();
+    let (lobby, mut spawns) = lobby.single_mut();
     for (
         entity,
         mut client,
@@ -111,6 +367,8 @@ fn init_clients(
         mut game_mode,
         mut pos,
         mut is_flat,
+        mut scopes,
+        username,
     ) in clients.iter_mut()
     {
         entity_layer.0 = lobby;
@@ -118,8 +376,12 @@ fn init_clients(
         visible_entity_layers.0.insert(lobby);
         is_flat.0 = true;
 
-        pos.set([0.0, 61.0, 0.0]);
+        pos.set(spawns.next());
         *game_mode = GameMode::Adventure;
+        scopes.add(commands::PLAYER_SCOPE);
+        if config.server.operators.contains(&username.0) {
+            scopes.add(commands::OPERATOR_SCOPE);
+        }
 
         commands.entity(entity).insert((LobbyPlayer,));
 
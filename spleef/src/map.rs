@@ -0,0 +1,254 @@
+use crate::{
+    area::Area,
+    config::{ConfigError, ModeRules},
+    level::WOOL,
+    power_ups::PowerUp,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+use valence::{prelude::*, DEFAULT_TPS};
+
+// Shipped inside every map folder next to the region files
+pub const METADATA_FILE: &str = "minigame.toml";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MapMeta {
+    // Only chunks and blocks inside this area are loaded and scanned
+    pub area: Area,
+    pub spawns: Vec<[f64; 3]>,
+    // Players below this height are eliminated
+    // Defaults to the lowest floor's elimination height, or the bottom of area
+    pub elimination_y: Option<f64>,
+    // Blocks that can be broken during the match with the default durability
+    // Defaults to every wool colour if palette is empty too
+    #[serde(default)]
+    pub destructible: Vec<BlockName>,
+    // Per block durability and behaviour, overrides destructible
+    #[serde(default)]
+    pub palette: Vec<PaletteEntry>,
+    // Class name -> region that selects it
+    #[serde(default)]
+    pub triggers: HashMap<String, TriggerRegion>,
+    // Floor of single floor modes: repainted every round in Block Party, the Sumo platform
+    pub floor: Option<Area>,
+    // Stacked floors of multi-floor maps, e.g. for TNT Run
+    #[serde(default)]
+    pub floors: Vec<FloorMeta>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FloorMeta {
+    pub area: Area,
+    // Players below this height have fallen off the floor, defaults to one block under it
+    pub elimination_y: Option<f64>,
+}
+
+impl FloorMeta {
+    pub fn elimination_y(&self) -> f64 {
+        self.elimination_y.unwrap_or(self.area.min().y as f64 - 1.0)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct BlockName(pub BlockKind);
+
+impl TryFrom<String> for BlockName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(&name);
+        BlockKind::from_str(name)
+            .map(Self)
+            .ok_or_else(|| format!("unknown block {name:?}"))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PaletteEntry {
+    pub block: BlockName,
+    // Ticks of standing on the block before it breaks, 1 breaks instantly
+    #[serde(default = "PaletteEntry::default_durability")]
+    pub durability: u16,
+    // Nothing can break it, e.g. arena borders
+    #[serde(default)]
+    pub indestructible: bool,
+    pub power_up: Option<PowerUp>,
+}
+
+impl PaletteEntry {
+    fn default_durability() -> u16 {
+        BlockPalette::DEFAULT_DURABILITY
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TriggerRegion {
+    Area(Area),
+    // Every block of this kind inside the map area, extended upwards so players can stand in it
+    Block { block: BlockName },
+}
+
+impl MapMeta {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = dir.as_ref().join(METADATA_FILE);
+        let data = std::fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        let meta: MapMeta =
+            toml::from_str(&data).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        if meta.spawns.is_empty() {
+            return Err(ConfigError::Invalid(format!(
+                "{} must declare at least one spawn",
+                path.display()
+            )));
+        }
+        if let Some(entry) = meta.palette.iter().find(|entry| entry.durability == 0) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: durability of {} must be at least 1",
+                path.display(),
+                entry.block.0.to_str()
+            )));
+        }
+        Ok(meta)
+    }
+
+    // Checks what the mode needs from the map
+    pub fn validate_mode(&self, mode: &str, rules: &ModeRules) -> Result<(), ConfigError> {
+        let needs_floor = rules.block_party.is_some() || rules.sumo.is_some();
+        if needs_floor && self.floor.is_none() {
+            return Err(ConfigError::Invalid(format!(
+                "mode {mode:?} needs a floor in the arena's {METADATA_FILE}"
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn spawn_points(&self) -> SpawnPoints {
+        SpawnPoints::new(self.spawns.iter().map(|&pos| pos.into()).collect())
+    }
+
+    pub fn elimination_y(&self) -> f64 {
+        let lowest_floor = self
+            .floors
+            .iter()
+            .map(FloorMeta::elimination_y)
+            .min_by(f64::total_cmp);
+        self.elimination_y
+            .or(lowest_floor)
+            .unwrap_or(self.area.min().y as f64)
+    }
+
+    // Elimination heights of the floors, top to bottom
+    pub fn floor_heights(&self) -> Vec<f64> {
+        let mut heights: Vec<f64> = self.floors.iter().map(FloorMeta::elimination_y).collect();
+        heights.sort_by(|a, b| b.total_cmp(a));
+        heights
+    }
+
+    pub fn palette(&self) -> BlockPalette {
+        let destructible: Vec<BlockKind> =
+            if self.destructible.is_empty() && self.palette.is_empty() {
+                WOOL.iter().map(|state| state.to_kind()).collect()
+            } else {
+                self.destructible.iter().map(|name| name.0).collect()
+            };
+        let mut blocks: HashMap<BlockKind, BlockBehaviour> = destructible
+            .into_iter()
+            .map(|kind| (kind, BlockBehaviour::default()))
+            .collect();
+        for entry in self.palette.iter() {
+            if entry.indestructible {
+                blocks.remove(&entry.block.0);
+                continue;
+            }
+            let behaviour = BlockBehaviour {
+                durability: entry.durability,
+                power_up: entry.power_up,
+            };
+            blocks.insert(entry.block.0, behaviour);
+        }
+        BlockPalette { blocks }
+    }
+}
+
+// Metadata of every map from the config
+#[derive(Resource)]
+pub struct Maps {
+    pub lobby: MapMeta,
+    pub arena: MapMeta,
+}
+
+impl Maps {
+    pub fn load(lobby: &Path, arena: &Path) -> Result<Self, ConfigError> {
+        Ok(Self {
+            lobby: MapMeta::load(lobby)?,
+            arena: MapMeta::load(arena)?,
+        })
+    }
+}
+
+// Where players appear when they enter the layer
+#[derive(Component)]
+pub struct SpawnPoints {
+    points: Vec<DVec3>,
+    next: usize,
+}
+
+impl SpawnPoints {
+    pub fn new(points: Vec<DVec3>) -> Self {
+        Self { points, next: 0 }
+    }
+
+    // Cycles through the points, so players don't spawn inside each other
+    pub fn next(&mut self) -> DVec3 {
+        let pos = self.points[self.next % self.points.len()];
+        self.next = self.next.wrapping_add(1);
+        pos
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockBehaviour {
+    pub durability: u16,
+    pub power_up: Option<PowerUp>,
+}
+
+impl Default for BlockBehaviour {
+    fn default() -> Self {
+        Self {
+            durability: BlockPalette::DEFAULT_DURABILITY,
+            power_up: None,
+        }
+    }
+}
+
+// Blocks of the arena that can be broken, everything else is indestructible
+#[derive(Component, Debug, Clone, Default)]
+pub struct BlockPalette {
+    blocks: HashMap<BlockKind, BlockBehaviour>,
+}
+
+impl BlockPalette {
+    // 5 seconds of standing
+    pub const DEFAULT_DURABILITY: u16 = DEFAULT_TPS.get() as u16 * 5;
+
+    // Every destructible block breaks after the same time, e.g. in TNT Run
+    pub fn with_durability(mut self, durability: u16) -> Self {
+        for behaviour in self.blocks.values_mut() {
+            behaviour.durability = durability;
+        }
+        self
+    }
+
+    pub fn get(&self, state: BlockState) -> Option<&BlockBehaviour> {
+        self.blocks.get(&state.to_kind())
+    }
+
+    pub fn is_destructible(&self, state: BlockState) -> bool {
+        self.get(state).is_some()
+    }
+
+    pub fn durability(&self, state: BlockState) -> Option<u16> {
+        self.get(state).map(|behaviour| behaviour.durability)
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{
+    area::Area,
+    classes::ClassRegistry,
+    map::{MapMeta, SpawnPoints, TriggerRegion},
+};
+use std::{collections::HashMap, str::FromStr};
+use valence::{
+    entity::text_display::{self, TextDisplayEntityBundle},
+    nbt::value::ValueRef,
+    prelude::*,
+    text::TextContent,
+};
+
+// Parsed from the first line of a sign, e.g. [trigger:mage]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Marker {
+    Spawn,
+    Trigger(String),
+    // Text display with the rest of the sign lines
+    Hologram,
+    Region(String),
+    // Bounding box of all border signs limits the arena in x and z
+    Border,
+}
+
+impl Marker {
+    pub fn parse(line: &str) -> Option<Self> {
+        let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+        let (kind, arg) = match inner.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg.trim())),
+            None => (inner, None),
+        };
+        match (kind.trim().to_lowercase().as_str(), arg) {
+            ("spawn", None) => Some(Marker::Spawn),
+            ("trigger", Some(class)) if !class.is_empty() => {
+                Some(Marker::Trigger(class.to_lowercase()))
+            }
+            ("hologram", None) => Some(Marker::Hologram),
+            ("region", Some(name)) if !name.is_empty() => Some(Marker::Region(name.to_owned())),
+            ("border", None) => Some(Marker::Border),
+            _ => None,
+        }
+    }
+}
+
+pub struct Hologram {
+    pub pos: BlockPos,
+    pub text: Text,
+}
+
+// Everything the map builder annotated with signs
+// Areas are bounding boxes of all signs with the same marker
+#[derive(Default)]
+pub struct MapMarkers {
+    pub spawns: Vec<DVec3>,
+    pub triggers: HashMap<String, Area>,
+    pub holograms: Vec<Hologram>,
+    pub regions: HashMap<String, Area>,
+    pub border: Option<Area>,
+}
+
+// Named areas of a layer, from [region:name] signs
+#[derive(Component, Default)]
+pub struct Regions(pub HashMap<String, Area>);
+
+pub fn extract_lines_from_sign_nbt(nbt: &Compound) -> Option<Vec<Text>> {
+    let text = nbt.get("front_text").map(|x| x.as_value_ref())?;
+    let ValueRef::Compound(text) = text else {
+        return None;
+    };
+    let text = text.get("messages").map(|x| x.as_value_ref())?;
+    let ValueRef::List(text) = text else {
+        return None;
+    };
+    // signs always have 4 lines
+    let lines = (0..4)
+        .map_while(|i| text.get(i))
+        .filter_map(|line| match line {
+            ValueRef::String(line) => Text::from_str(line).ok(),
+            _ => None,
+        })
+        .collect();
+    Some(lines)
+}
+
+fn plain_text(text: &Text) -> Option<&str> {
+    let TextContent::Text { text } = &text.content else {
+        return None;
+    };
+    Some(text.as_ref())
+}
+
+fn merge_area(areas: &mut HashMap<String, Area>, name: String, pos: BlockPos) {
+    let block_area = Area::new(pos, pos);
+    areas
+        .entry(name)
+        .and_modify(|area| *area = area.merge(&block_area))
+        .or_insert(block_area);
+}
+
+fn join_lines(lines: &[Text]) -> Text {
+    lines
+        .iter()
+        .cloned()
+        .reduce(|text, line| text + "\n" + line)
+        .unwrap_or_default()
+}
+
+impl MapMarkers {
+    // Collects marker signs inside area and replaces them with air
+    // Signs with just a class id on the first line become the class label
+    pub fn scan(layer: &mut LayerBundle, area: &Area, registry: &ClassRegistry) -> Self {
+        let mut markers = Self::default();
+        for pos in area.iter_block_pos() {
+            let Some(block) = layer.chunk.block(pos) else {
+                continue;
+            };
+            if !block.state.to_kind().to_str().ends_with("sign") {
+                continue;
+            }
+            let Some(lines) = block.nbt.and_then(extract_lines_from_sign_nbt) else {
+                continue;
+            };
+            let Some(first) = lines.first().and_then(plain_text) else {
+                continue;
+            };
+            if let Some(marker) = Marker::parse(first) {
+                markers.add(pos, marker, &lines[1..]);
+            } else if let Some(class) = registry.find(first) {
+                markers.holograms.push(Hologram {
+                    pos,
+                    text: registry.get(class).display.clone().into_text(),
+                });
+            } else {
+                continue;
+            }
+            layer.chunk.set_block(pos, BlockState::AIR);
+        }
+        markers
+    }
+
+    fn add(&mut self, pos: BlockPos, marker: Marker, rest: &[Text]) {
+        match marker {
+            Marker::Spawn => self.spawns.push(DVec3::new(
+                pos.x as f64 + 0.5,
+                pos.y as f64,
+                pos.z as f64 + 0.5,
+            )),
+            Marker::Trigger(class) => merge_area(&mut self.triggers, class, pos),
+            Marker::Hologram => self.holograms.push(Hologram {
+                pos,
+                text: join_lines(rest),
+            }),
+            Marker::Region(name) => merge_area(&mut self.regions, name, pos),
+            Marker::Border => {
+                let block_area = Area::new(pos, pos);
+                self.border = Some(match self.border {
+                    Some(border) => border.merge(&block_area),
+                    None => block_area,
+                });
+            }
+        }
+    }
+
+    // Trigger signs override triggers from the map metadata
+    pub fn merge_triggers(
+        &self,
+        triggers: &HashMap<String, TriggerRegion>,
+    ) -> HashMap<String, TriggerRegion> {
+        let mut triggers = triggers.clone();
+        for (class, area) in self.triggers.iter() {
+            // so players can jump inside the trigger
+            let area = area.expand(BlockPos::new(0, 2, 0));
+            triggers.insert(class.clone(), TriggerRegion::Area(area));
+        }
+        triggers
+    }
+
+    pub fn spawn_holograms(&self, layer_id: Entity, commands: &mut Commands) {
+        for hologram in self.holograms.iter() {
+            let pos = hologram.pos;
+            commands.spawn(TextDisplayEntityBundle {
+                layer: EntityLayerId(layer_id),
+                text_display_text: text_display::Text(hologram.text.clone()),
+                position: Position([pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 - 0.5].into()),
+                look: Look::new(180.0, 0.0),
+                ..Default::default()
+            });
+        }
+    }
+
+    // Spawn signs override spawns from the map metadata
+    pub fn spawn_points(&self, meta: &MapMeta) -> SpawnPoints {
+        if self.spawns.is_empty() {
+            meta.spawn_points()
+        } else {
+            SpawnPoints::new(self.spawns.clone())
+        }
+    }
+
+    pub fn regions(&self) -> Regions {
+        Regions(self.regions.clone())
+    }
+
+    // Border signs only mark x and z, players can still jump and fall
+    // over the whole height of the map area
+    pub fn arena_area(&self, map_area: &Area) -> Area {
+        let Some(border) = self.border else {
+            return *map_area;
+        };
+        Area::new(
+            [border.min().x, map_area.min().y, border.min().z],
+            [border.max().x, map_area.max().y, border.max().z],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_markers() {
+        assert_eq!(Marker::parse("[spawn]"), Some(Marker::Spawn));
+        assert_eq!(
+            Marker::parse(" [Trigger: Mage] "),
+            Some(Marker::Trigger("mage".into()))
+        );
+        assert_eq!(Marker::parse("[border]"), Some(Marker::Border));
+        assert_eq!(Marker::parse("[trigger:]"), None);
+        assert_eq!(
+            Marker::parse("[region:a]"),
+            Some(Marker::Region("a".into()))
+        );
+        assert_eq!(Marker::parse("[region:]"), None);
+        assert_eq!(Marker::parse("spawn"), None);
+    }
+
+    #[test]
+    fn regions_are_bounding_boxes() {
+        let mut markers = MapMarkers::default();
+        markers.add(BlockPos::new(1, 2, 3), Marker::Region("a".into()), &[]);
+        markers.add(BlockPos::new(-1, 5, 0), Marker::Region("a".into()), &[]);
+        markers.add(BlockPos::new(9, 9, 9), Marker::Region("b".into()), &[]);
+        let Regions(regions) = markers.regions();
+        assert_eq!(regions["a"], Area::new([-1, 2, 0], [1, 5, 3]));
+        assert_eq!(regions["b"], Area::new([9, 9, 9], [9, 9, 9]));
+    }
+
+    #[test]
+    fn border_keeps_map_height() {
+        let map_area = Area::new([-100, 0, -100], [100, 200, 100]);
+        let mut markers = MapMarkers::default();
+        assert_eq!(markers.arena_area(&map_area), map_area);
+
+        markers.add(BlockPos::new(-20, 64, -30), Marker::Border, &[]);
+        markers.add(BlockPos::new(20, 65, 30), Marker::Border, &[]);
+        assert_eq!(
+            markers.arena_area(&map_area),
+            Area::new([-20, 0, -30], [20, 200, 30])
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    classes::Cooldown,
+    damage::BlockDestroyedEvent,
+    game::{self, Match},
+    level::ArenaPlayer,
+    map::BlockPalette,
+};
+use serde::Deserialize;
+use valence::{
+    entity::{
+        entity::NoGravity,
+        item::{ItemEntityBundle, Stack},
+    },
+    prelude::*,
+};
+
+// Dropped by palette blocks when they are destroyed
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerUp {
+    // Launches the player up and where they're looking
+    Leap,
+    // Class ability is ready right away
+    ResetCooldown,
+}
+
+impl PowerUp {
+    fn icon(self) -> ItemKind {
+        match self {
+            PowerUp::Leap => ItemKind::Feather,
+            PowerUp::ResetCooldown => ItemKind::Clock,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PowerUp::Leap => "Leap",
+            PowerUp::ResetCooldown => "Cooldown Reset",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PowerUpDrop(pub PowerUp);
+
+const PICKUP_DISTANCE: f64 = 1.5;
+
+pub fn drop_power_ups(
+    mut destroyed: EventReader<BlockDestroyedEvent>,
+    arenas: Query<&BlockPalette>,
+    mut commands: Commands,
+) {
+    for event in destroyed.read() {
+        let Some(power_up) = arenas
+            .get(event.layer)
+            .ok()
+            .and_then(|palette| palette.get(event.state))
+            .and_then(|behaviour| behaviour.power_up)
+        else {
+            continue;
+        };
+        let pos = event.pos;
+        commands.spawn((
+            ItemEntityBundle {
+                item_stack: Stack(ItemStack::new(power_up.icon(), 1, None)),
+                // the block under it is gone, so keep it in place on the client too
+                entity_no_gravity: NoGravity(true),
+                position: Position([pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5].into()),
+                layer: EntityLayerId(event.layer),
+                ..Default::default()
+            },
+            PowerUpDrop(power_up),
+        ));
+    }
+}
+
+pub fn pick_up_power_ups(
+    drops: Query<(Entity, &Position, &EntityLayerId, &PowerUpDrop)>,
+    mut players: Query<(Entity, &mut Client, &Position, &Look, &EntityLayerId), With<ArenaPlayer>>,
+    matches: Query<&Match>,
+    mut commands: Commands,
+) {
+    for (drop, drop_pos, drop_layer, power_up) in drops.iter() {
+        // leftovers from the previous match
+        if !game::is_running(&matches, drop_layer) {
+            commands.entity(drop).insert(Despawned);
+            continue;
+        }
+        let Some((player, mut client, _, look, _)) =
+            players.iter_mut().find(|(_, _, pos, _, layer)| {
+                layer.0 == drop_layer.0
+                    && (pos.0 + DVec3::new(0.0, 0.9, 0.0)).distance(drop_pos.0) < PICKUP_DISTANCE
+            })
+        else {
+            continue;
+        };
+        match power_up.0 {
+            PowerUp::Leap => {
+                let dir = look.vec();
+                client.set_velocity([dir.x * 12.0, 10.0, dir.z * 12.0]);
+            }
+            PowerUp::ResetCooldown => {
+                commands.entity(player).remove::<Cooldown>();
+            }
+        }
+        client.send_chat_message("Picked up ".into_text() + power_up.0.name().bold());
+        commands.entity(drop).insert(Despawned);
+    }
+}
@@ -0,0 +1,316 @@
+use valence::{protocol::Direction, BlockPos, BlockState, ChunkLayer, DVec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub block: BlockPos,
+    pub state: BlockState,
+    // Face of the block the ray entered through
+    pub face: Direction,
+    pub point: DVec3,
+    // Distance from the origin to point
+    pub distance: f64,
+}
+
+// Face we cross when moving one block along axis in step direction
+fn entered_face(axis: usize, step: i32) -> Direction {
+    match (axis, step > 0) {
+        (0, true) => Direction::West,
+        (0, false) => Direction::East,
+        (1, true) => Direction::Down,
+        (1, false) => Direction::Up,
+        (2, true) => Direction::North,
+        _ => Direction::South,
+    }
+}
+
+fn dominant_axis(dir: [f64; 3]) -> usize {
+    let abs = dir.map(f64::abs);
+    if abs[0] >= abs[1] && abs[0] >= abs[2] {
+        0
+    } else if abs[1] >= abs[2] {
+        1
+    } else {
+        2
+    }
+}
+
+// First non-air block on the way from origin, see raycast_with
+// Blocks in unloaded chunks are skipped
+pub fn raycast(layer: &ChunkLayer, origin: DVec3, dir: DVec3, max_dist: f64) -> Option<RaycastHit> {
+    raycast_with(origin, dir, max_dist, |pos| {
+        layer
+            .block(pos)
+            .map(|block| block.state)
+            .filter(|state| !state.is_air())
+    })
+}
+
+// Walks every block the ray passes through in order (Amanatides-Woo)
+// and returns the first one where solid_at gives a state
+pub fn raycast_with(
+    origin: DVec3,
+    dir: DVec3,
+    max_dist: f64,
+    mut solid_at: impl FnMut(BlockPos) -> Option<BlockState>,
+) -> Option<RaycastHit> {
+    let dir = dir.normalize_or_zero();
+    if dir == DVec3::ZERO || max_dist.is_nan() || max_dist < 0.0 {
+        return None;
+    }
+    let origin_arr = origin.to_array();
+    let dir_arr = dir.to_array();
+
+    let start: BlockPos = origin.into();
+    let mut block = [start.x, start.y, start.z];
+    // Per axis: step direction, distance along the ray to the next block boundary
+    // and distance between two boundaries
+    let axes = [0, 1, 2].map(|axis| {
+        let d = dir_arr[axis];
+        let offset = origin_arr[axis] - block[axis] as f64;
+        if d > 0.0 {
+            (1, (1.0 - offset) / d, 1.0 / d)
+        } else if d < 0.0 {
+            (-1, offset / -d, -1.0 / d)
+        } else {
+            (0, f64::INFINITY, f64::INFINITY)
+        }
+    });
+    let step = axes.map(|(step, _, _)| step);
+    let t_delta = axes.map(|(_, _, delta)| delta);
+    let mut t_max = axes.map(|(_, t_max, _)| t_max);
+
+    // Origin inside a block counts as entering it head on
+    let main_axis = dominant_axis(dir_arr);
+    let mut face = entered_face(main_axis, step[main_axis]);
+    let mut t = 0.0;
+    loop {
+        let pos = BlockPos::new(block[0], block[1], block[2]);
+        if let Some(state) = solid_at(pos) {
+            return Some(RaycastHit {
+                block: pos,
+                state,
+                face,
+                point: origin + dir * t,
+                distance: t,
+            });
+        }
+
+        let axis = if t_max[0] < t_max[1] && t_max[0] < t_max[2] {
+            0
+        } else if t_max[1] < t_max[2] {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_dist {
+            return None;
+        }
+        t = t_max[axis];
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        face = entered_face(axis, step[axis]);
+    }
+}
+
+// Distance along the ray to the box with corners min and max
+// None if the ray misses it or the box is further than max_dist
+pub fn raycast_aabb(
+    origin: DVec3,
+    dir: DVec3,
+    max_dist: f64,
+    min: DVec3,
+    max: DVec3,
+) -> Option<f64> {
+    let dir = dir.normalize_or_zero();
+    if dir == DVec3::ZERO || max_dist.is_nan() {
+        return None;
+    }
+    let mut t_near = f64::NEG_INFINITY;
+    let mut t_far = f64::INFINITY;
+    for axis in 0..3 {
+        let (o, d) = (origin[axis], dir[axis]);
+        if d == 0.0 {
+            // parallel to the slab, the ray is either always or never inside it
+            if o < min[axis] || o > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - o) / d;
+        let t2 = (max[axis] - o) / d;
+        t_near = t_near.max(t1.min(t2));
+        t_far = t_far.min(t1.max(t2));
+    }
+    if t_near > t_far || t_far < 0.0 || t_near > max_dist {
+        return None;
+    }
+    Some(t_near.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn cast(
+        solid: &[[i32; 3]],
+        origin: [f64; 3],
+        dir: [f64; 3],
+        max_dist: f64,
+    ) -> Option<RaycastHit> {
+        let solid: HashSet<BlockPos> = solid.iter().map(|&pos| pos.into()).collect();
+        raycast_with(origin.into(), dir.into(), max_dist, |pos| {
+            solid.contains(&pos).then_some(BlockState::STONE)
+        })
+    }
+
+    #[test]
+    fn axis_aligned_rays() {
+        let hit = cast(&[[5, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 10.0).unwrap();
+        assert_eq!(hit.block, BlockPos::new(5, 0, 0));
+        assert_eq!(hit.face, Direction::West);
+        assert!((hit.distance - 4.5).abs() < 1e-9);
+        assert!((hit.point - DVec3::new(5.0, 0.5, 0.5)).length() < 1e-9);
+
+        let hit = cast(&[[0, -3, 0]], [0.5, 0.5, 0.5], [0.0, -1.0, 0.0], 10.0).unwrap();
+        assert_eq!(hit.block, BlockPos::new(0, -3, 0));
+        assert_eq!(hit.face, Direction::Up);
+        assert!((hit.distance - 2.5).abs() < 1e-9);
+
+        let hit = cast(&[[0, 0, -2]], [0.5, 0.5, 0.5], [0.0, 0.0, -1.0], 10.0).unwrap();
+        assert_eq!(hit.face, Direction::South);
+    }
+
+    #[test]
+    fn ray_stops_at_max_dist() {
+        assert_eq!(
+            cast(&[[5, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 4.0),
+            None
+        );
+        assert_eq!(
+            cast(&[[5, 0, 0]], [0.5, 0.5, 0.5], [-1.0, 0.0, 0.0], 10.0),
+            None
+        );
+    }
+
+    #[test]
+    fn diagonal_rays() {
+        // slightly off the diagonal, so the block next to it is entered first
+        let hit = cast(
+            &[[1, 0, 0], [2, 0, 2]],
+            [0.5, 0.5, 0.4],
+            [1.0, 0.0, 1.0],
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(hit.block, BlockPos::new(1, 0, 0));
+        assert_eq!(hit.face, Direction::West);
+
+        let hit = cast(&[[3, 3, 3]], [0.5, 0.5, 0.5], [1.0, 1.0, 1.0], 10.0).unwrap();
+        assert_eq!(hit.block, BlockPos::new(3, 3, 3));
+        assert!((hit.point - DVec3::splat(3.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn ray_starting_inside_a_block() {
+        let hit = cast(&[[0, 0, 0]], [0.5, 0.5, 0.5], [0.0, 1.0, 0.0], 10.0).unwrap();
+        assert_eq!(hit.block, BlockPos::new(0, 0, 0));
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.face, Direction::Down);
+    }
+
+    #[test]
+    fn zero_length_rays() {
+        assert_eq!(
+            cast(&[[0, 0, 0]], [0.5, 0.5, 0.5], [0.0, 0.0, 0.0], 10.0),
+            None
+        );
+        // only the origin block is checked
+        assert!(cast(&[[0, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 0.0).is_some());
+        assert_eq!(
+            cast(&[[1, 0, 0]], [0.5, 0.5, 0.5], [1.0, 0.0, 0.0], 0.0),
+            None
+        );
+    }
+
+    fn unit_box() -> (DVec3, DVec3) {
+        (DVec3::new(2.0, 0.0, 0.0), DVec3::new(3.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn aabb_axis_aligned() {
+        let (min, max) = unit_box();
+        let dist = raycast_aabb(DVec3::new(0.0, 0.5, 0.5), DVec3::X, 10.0, min, max);
+        assert_eq!(dist, Some(2.0));
+        assert_eq!(
+            raycast_aabb(DVec3::new(0.0, 0.5, 0.5), DVec3::NEG_X, 10.0, min, max),
+            None
+        );
+        assert_eq!(
+            raycast_aabb(DVec3::new(0.0, 0.5, 0.5), DVec3::X, 1.5, min, max),
+            None
+        );
+    }
+
+    #[test]
+    fn aabb_origin_on_slab_plane() {
+        let (min, max) = unit_box();
+        // y and z components are 0 while the origin lies on the box faces
+        let dist = raycast_aabb(DVec3::new(0.0, 0.0, 1.0), DVec3::X, 10.0, min, max);
+        assert_eq!(dist, Some(2.0));
+        let dist = raycast_aabb(DVec3::new(0.0, 1.0, 0.0), DVec3::X, 10.0, min, max);
+        assert_eq!(dist, Some(2.0));
+        assert_eq!(
+            raycast_aabb(DVec3::new(0.0, 1.5, 0.5), DVec3::X, 10.0, min, max),
+            None
+        );
+    }
+
+    #[test]
+    fn aabb_diagonal() {
+        let (min, max) = unit_box();
+        let dist = raycast_aabb(
+            DVec3::new(0.0, -1.5, 0.5),
+            DVec3::new(1.0, 1.0, 0.0),
+            10.0,
+            min,
+            max,
+        )
+        .unwrap();
+        assert!((dist - 2.0 * 2f64.sqrt()).abs() < 1e-9);
+        assert_eq!(
+            raycast_aabb(
+                DVec3::new(0.0, 2.5, 0.5),
+                DVec3::new(1.0, 1.0, 0.0),
+                10.0,
+                min,
+                max
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn aabb_origin_inside() {
+        let (min, max) = unit_box();
+        let dist = raycast_aabb(DVec3::new(2.5, 0.5, 0.5), DVec3::Y, 10.0, min, max);
+        assert_eq!(dist, Some(0.0));
+    }
+
+    #[test]
+    fn aabb_zero_length() {
+        let (min, max) = unit_box();
+        assert_eq!(
+            raycast_aabb(DVec3::new(2.5, 0.5, 0.5), DVec3::ZERO, 10.0, min, max),
+            None
+        );
+        assert_eq!(
+            raycast_aabb(DVec3::new(2.5, 0.5, 0.5), DVec3::X, 0.0, min, max),
+            Some(0.0)
+        );
+        assert_eq!(
+            raycast_aabb(DVec3::new(0.0, 0.5, 0.5), DVec3::X, 0.0, min, max),
+            None
+        );
+    }
+}
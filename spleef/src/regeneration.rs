@@ -0,0 +1,147 @@
+use crate::{
+    classes::{PLAYER_HEIGHT, PLAYER_WIDTH},
+    damage::BlockDestroyedEvent,
+    dynamic_blocks::DynamicBlocks,
+    level::{ArenaPlayer, RestoreArena},
+    map::BlockPalette,
+};
+use std::collections::VecDeque;
+use valence::{prelude::*, DEFAULT_TPS};
+
+struct PendingBlock {
+    pos: BlockPos,
+    state: BlockState,
+    tick: i64,
+}
+
+// Destroyed blocks of the arena come back after a delay, for practice arenas
+#[derive(Component)]
+pub struct Regeneration {
+    delay: i64,
+    // Sorted by tick
+    pending: VecDeque<PendingBlock>,
+    // Regenerated blocks that are still cracked
+    healing: Vec<BlockPos>,
+}
+
+impl Regeneration {
+    // How long the cracks take to disappear, the block is weaker until then
+    const HEAL_TICKS: u16 = DEFAULT_TPS.get() as u16;
+    // Retry delay when somebody stands where the block should be
+    const BLOCKED_RETRY_TICKS: i64 = DEFAULT_TPS.get() as i64 / 2;
+
+    pub fn new(delay: i64) -> Self {
+        Self {
+            delay,
+            pending: VecDeque::new(),
+            healing: Vec::new(),
+        }
+    }
+}
+
+fn player_occupies(player: DVec3, block: BlockPos) -> bool {
+    let half_width = PLAYER_WIDTH / 2.0;
+    let min = DVec3::new(block.x as f64, block.y as f64, block.z as f64);
+    let max = min + DVec3::ONE;
+    player.x + half_width > min.x
+        && player.x - half_width < max.x
+        && player.y + PLAYER_HEIGHT > min.y
+        && player.y < max.y
+        && player.z + half_width > min.z
+        && player.z - half_width < max.z
+}
+
+pub fn queue_regeneration(
+    server: Res<Server>,
+    mut destroyed: EventReader<BlockDestroyedEvent>,
+    mut arenas: Query<&mut Regeneration>,
+) {
+    for event in destroyed.read() {
+        let Ok(mut regeneration) = arenas.get_mut(event.layer) else {
+            continue;
+        };
+        let tick = server.current_tick() + regeneration.delay;
+        regeneration.pending.push_back(PendingBlock {
+            pos: event.pos,
+            state: event.state,
+            tick,
+        });
+    }
+}
+
+pub fn regenerate_blocks(
+    server: Res<Server>,
+    mut arenas: Query<(
+        Entity,
+        &mut Regeneration,
+        &mut ChunkLayer,
+        &mut DynamicBlocks,
+        &BlockPalette,
+        Has<RestoreArena>,
+    )>,
+    players: Query<(&Position, &EntityLayerId), With<ArenaPlayer>>,
+) {
+    let current_tick = server.current_tick();
+    for (arena, mut regeneration, mut layer, mut dynamic, palette, restoring) in arenas.iter_mut() {
+        let regeneration = regeneration.as_mut();
+        // restoring brings back everything anyway
+        if restoring {
+            regeneration.pending.clear();
+            regeneration.healing.clear();
+            continue;
+        }
+
+        regeneration.healing.retain(|&pos| {
+            let Some(hp) = dynamic.hp(pos) else {
+                // destroyed again while healing
+                return false;
+            };
+            let Some(durability) = layer.block(pos).and_then(|b| palette.durability(b.state))
+            else {
+                return false;
+            };
+            let step = (durability / Regeneration::HEAL_TICKS).max(1);
+            let hp = hp.saturating_add(step).min(durability);
+            dynamic.insert(pos, hp);
+            hp < durability
+        });
+
+        let mut blocked = vec![];
+        while regeneration
+            .pending
+            .front()
+            .is_some_and(|block| block.tick <= current_tick)
+        {
+            let Some(mut block) = regeneration.pending.pop_front() else {
+                break;
+            };
+            let occupied = players.iter().any(|(pos, player_layer)| {
+                player_layer.0 == arena && player_occupies(pos.0, block.pos)
+            });
+            // something else was placed there in the meantime
+            let replaced = layer
+                .block(block.pos)
+                .is_some_and(|current| !current.state.is_air());
+            if replaced {
+                continue;
+            }
+            if occupied {
+                block.tick = current_tick + Regeneration::BLOCKED_RETRY_TICKS;
+                blocked.push(block);
+                continue;
+            }
+            layer.set_block(block.pos, block.state);
+            if palette.is_destructible(block.state) {
+                // starts fully cracked and heals, so players see it coming back
+                dynamic.insert(block.pos, 1);
+                regeneration.healing.push(block.pos);
+            }
+        }
+        for block in blocked {
+            let index = regeneration
+                .pending
+                .partition_point(|pending| pending.tick <= block.tick);
+            regeneration.pending.insert(index, block);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    classes::ClassName,
+    game::{Match, MatchPhase, Spectator},
+    level::{ArenaLayer, ArenaPlayer},
+    teams::Team,
+};
+use std::borrow::Cow;
+use valence::{
+    prelude::*,
+    protocol::{
+        packets::play::{
+            team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
+            TeamS2c,
+        },
+        WritePacket,
+    },
+    scoreboard::{
+        Objective, ObjectiveBundle, ObjectiveDisplay, ObjectivePosition, ObjectiveScores,
+    },
+    DEFAULT_TPS,
+};
+
+// Sidebar has room for 15 lines, one is the match status
+const MAX_SIDEBAR_PLAYERS: usize = 14;
+
+// Sidebar objective of an arena instance, only visible inside the arena
+#[derive(Component)]
+pub struct Sidebar {
+    objective: Entity,
+    lines: Vec<String>,
+}
+
+pub fn create_sidebar(arena: Entity, commands: &mut Commands) -> Sidebar {
+    let objective = commands
+        .spawn(ObjectiveBundle {
+            name: Objective::new(format!("arena{}", arena.index())),
+            display: ObjectiveDisplay("Spleef".bold()),
+            position: ObjectivePosition::Sidebar,
+            layer: EntityLayerId(arena),
+            ..Default::default()
+        })
+        .id();
+    Sidebar {
+        objective,
+        lines: vec![],
+    }
+}
+
+// mm:ss, also used by the hud
+pub(crate) fn format_time(ticks: i64) -> String {
+    let seconds = ticks.max(0) / DEFAULT_TPS.get() as i64;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn status_line(phase: MatchPhase) -> String {
+    match phase {
+        MatchPhase::Waiting => "Waiting for players".into(),
+        MatchPhase::Countdown { timer } => {
+            let seconds = (timer + DEFAULT_TPS.get() as i64 - 1) / DEFAULT_TPS.get() as i64;
+            format!("Starting in {seconds}s")
+        }
+        MatchPhase::Running { elapsed } => format!("Time {}", format_time(elapsed)),
+        MatchPhase::Ending { .. } => "Game over".into(),
+    }
+}
+
+pub fn update_sidebars(
+    mut arenas: Query<(Entity, &Match, &mut Sidebar), With<ArenaLayer>>,
+    mut objectives: Query<&mut ObjectiveScores>,
+    players: Query<(&Username, &ClassName, &EntityLayerId), With<ArenaPlayer>>,
+) {
+    for (arena, game, mut sidebar) in arenas.iter_mut() {
+        let mut alive: Vec<_> = players
+            .iter()
+            .filter(|(_, _, layer)| layer.0 == arena)
+            .map(|(username, class, _)| format!("{} {}", username.0, class.0))
+            .collect();
+        alive.sort();
+        let lines: Vec<String> = std::iter::once(status_line(game.phase))
+            .chain(alive.into_iter().take(MAX_SIDEBAR_PLAYERS))
+            .collect();
+        if lines == sidebar.lines {
+            continue;
+        }
+        let Ok(mut scores) = objectives.get_mut(sidebar.objective) else {
+            continue;
+        };
+        for old in sidebar.lines.iter() {
+            if !lines.contains(old) {
+                scores.remove(old);
+            }
+        }
+        // sidebar is sorted by score, highest on top
+        for (i, line) in lines.iter().enumerate() {
+            scores.insert(line.clone(), (lines.len() - i) as i32);
+        }
+        sidebar.lines = lines;
+    }
+}
+
+// Tab list sections, the client sorts players by team name
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabGroup {
+    Arena,
+    // Arena player in a team mode, also colours the name tag
+    Team(Team),
+    Spectating,
+    Lobby,
+}
+
+impl TabGroup {
+    fn all() -> impl Iterator<Item = TabGroup> {
+        [TabGroup::Arena]
+            .into_iter()
+            .chain(Team::all().map(TabGroup::Team))
+            .chain([TabGroup::Spectating, TabGroup::Lobby])
+    }
+
+    fn team(self) -> &'static str {
+        match self {
+            TabGroup::Arena => "0_arena",
+            TabGroup::Team(team) => team.def().tab_team,
+            TabGroup::Spectating => "1_spectating",
+            TabGroup::Lobby => "2_lobby",
+        }
+    }
+
+    fn prefix(self) -> Text {
+        match self {
+            TabGroup::Arena => "[Arena] ".color(Color::GREEN),
+            TabGroup::Team(team) => format!("[{}] ", team.def().name).color(team.def().color),
+            TabGroup::Spectating => "[Spectating] ".color(Color::GRAY),
+            TabGroup::Lobby => "[Lobby] ".color(Color::YELLOW),
+        }
+    }
+
+    fn color(self) -> TeamColor {
+        match self {
+            TabGroup::Team(team) => team.def().tag_color,
+            _ => TeamColor::White,
+        }
+    }
+
+    fn of(is_playing: bool, is_spectating: bool, team: Option<&Team>) -> Self {
+        if is_spectating {
+            TabGroup::Spectating
+        } else if let (true, Some(team)) = (is_playing, team) {
+            TabGroup::Team(*team)
+        } else if is_playing {
+            TabGroup::Arena
+        } else {
+            TabGroup::Lobby
+        }
+    }
+}
+
+fn create_team_packet(group: TabGroup, members: Vec<&str>) -> TeamS2c<'_> {
+    TeamS2c {
+        team_name: group.team(),
+        mode: Mode::CreateTeam {
+            team_display_name: Cow::Owned(group.team().into_text()),
+            friendly_flags: TeamFlags::new(),
+            name_tag_visibility: NameTagVisibility::Always,
+            collision_rule: CollisionRule::Always,
+            team_color: group.color(),
+            team_prefix: Cow::Owned(group.prefix()),
+            team_suffix: Cow::Owned(Text::default()),
+            entities: members,
+        },
+    }
+}
+
+// Moves players between tab list sections as they join, play and get eliminated
+pub fn update_tab_groups(
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        &Username,
+        Option<&TabGroup>,
+        Has<ArenaPlayer>,
+        Has<Spectator>,
+        Option<&Team>,
+    )>,
+    mut commands: Commands,
+) {
+    let groups: Vec<_> = clients
+        .iter()
+        .map(|(e, _, username, current, playing, spectating, team)| {
+            (
+                e,
+                username.0.clone(),
+                current.copied(),
+                TabGroup::of(playing, spectating, team),
+            )
+        })
+        .collect();
+    let changed: Vec<_> = groups
+        .iter()
+        .filter(|(_, _, current, group)| *current != Some(*group))
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+
+    for (_, mut client, _, current, _, _, _) in clients.iter_mut() {
+        if current.is_none() {
+            // teams were never sent to this client
+            for group in TabGroup::all() {
+                let members = groups
+                    .iter()
+                    .filter(|(_, _, _, g)| *g == group)
+                    .map(|(_, username, _, _)| username.as_str())
+                    .collect();
+                client.write_packet(&create_team_packet(group, members));
+            }
+            continue;
+        }
+        for group in TabGroup::all() {
+            let entities: Vec<&str> = changed
+                .iter()
+                .filter(|(_, _, _, g)| *g == group)
+                .map(|(_, username, _, _)| username.as_str())
+                .collect();
+            if entities.is_empty() {
+                continue;
+            }
+            // joining a team removes the player from the previous one
+            client.write_packet(&TeamS2c {
+                team_name: group.team(),
+                mode: Mode::AddEntities { entities },
+            });
+        }
+    }
+
+    for (e, _, _, group) in changed {
+        commands.entity(*e).insert(*group);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    area::Area,
+    classes::{self, PlayerClass, ProjectileHit, Shooter},
+    config::SpleggRules,
+    damage::{BlockDamageEvent, DamageCause},
+    game::{ArenaBounds, Match},
+    hud::ActionBarMessage,
+    level::{ArenaLayer, ArenaPlayer, JoinArena},
+};
+use valence::{
+    entity::{egg::EggEntityBundle, entity::NoGravity, thrown_item::Item, Velocity},
+    interact_block::InteractBlockEvent,
+    interact_item::InteractItemEvent,
+    inventory::{player_inventory::PlayerInventory, HeldItem},
+    prelude::*,
+};
+
+// Last hotbar slots, so class kits keep theirs
+const LAUNCHER_SLOT: u8 = 8;
+const AMMO_SLOT: u8 = 7;
+
+// Arenas where only projectiles break blocks, standing and digging don't
+#[derive(Component)]
+pub struct Splegg;
+
+fn splegg_rules(game: &Match) -> Option<&SpleggRules> {
+    game.rules.splegg.as_ref()
+}
+
+#[derive(Component)]
+pub struct EggLauncher {
+    // Tick of the last shot, for the fire rate
+    last_shot: i64,
+}
+
+// Runs after init_class, which replaces the whole inventory with the kit
+pub fn give_launchers(
+    mut clients: Query<
+        (Entity, &mut Inventory, &JoinArena),
+        (With<ArenaPlayer>, Added<PlayerClass>),
+    >,
+    arenas: Query<&Match, With<Splegg>>,
+    mut commands: Commands,
+) {
+    for (e, mut inv, join) in clients.iter_mut() {
+        let Some(rules) = arenas.get(join.0).ok().and_then(splegg_rules) else {
+            continue;
+        };
+        inv.set_slot(
+            PlayerInventory::hotbar_to_slot(LAUNCHER_SLOT),
+            ItemStack::new(ItemKind::Egg, 1, None),
+        );
+        if let Some(ammo) = rules.ammo {
+            inv.set_slot(
+                PlayerInventory::hotbar_to_slot(AMMO_SLOT),
+                ItemStack::new(ItemKind::Snowball, ammo, None),
+            );
+        }
+        commands.entity(e).insert(EggLauncher { last_shot: 0 });
+    }
+}
+
+fn ammo_count(inv: &Inventory) -> i8 {
+    let stack = inv.slot(PlayerInventory::hotbar_to_slot(AMMO_SLOT));
+    if stack.item == ItemKind::Snowball {
+        stack.count
+    } else {
+        0
+    }
+}
+
+fn set_ammo(inv: &mut Inventory, count: i8) {
+    let stack = if count > 0 {
+        ItemStack::new(ItemKind::Snowball, count, None)
+    } else {
+        ItemStack::EMPTY
+    };
+    inv.set_slot(PlayerInventory::hotbar_to_slot(AMMO_SLOT), stack);
+}
+
+#[derive(Component)]
+pub struct SpleggEgg;
+
+pub fn launch_eggs(
+    server: Res<Server>,
+    mut clients: Query<
+        (
+            &HeldItem,
+            &mut Inventory,
+            &Position,
+            &Look,
+            &EntityLayerId,
+            &mut EggLauncher,
+        ),
+        With<ArenaPlayer>,
+    >,
+    arenas: Query<&Match, With<Splegg>>,
+    mut item_interacts: EventReader<InteractItemEvent>,
+    mut block_interacts: EventReader<InteractBlockEvent>,
+    mut commands: Commands,
+) {
+    let tick = server.current_tick();
+    let mut process = |client: Entity| {
+        let Ok((held, mut inv, pos, look, entity_layer, mut launcher)) = clients.get_mut(client)
+        else {
+            return;
+        };
+        if held.slot() != PlayerInventory::hotbar_to_slot(LAUNCHER_SLOT) {
+            return;
+        }
+        let Ok(game) = arenas.get(entity_layer.0) else {
+            return;
+        };
+        let Some(rules) = splegg_rules(game).filter(|_| game.is_running()) else {
+            return;
+        };
+        // also skips the item event sent together with the block one
+        if tick - launcher.last_shot < rules.cooldown_ticks {
+            return;
+        }
+        if rules.ammo.is_some() {
+            let ammo = ammo_count(&inv);
+            if ammo == 0 {
+                commands.entity(client).insert(ActionBarMessage(
+                    "Out of snowballs, refill at the edge of the arena".color(Color::RED),
+                ));
+                return;
+            }
+            set_ammo(&mut inv, ammo - 1);
+        }
+        launcher.last_shot = tick;
+
+        let shift: DVec3 = [0.0, 1.5, 0.0].into();
+        let egg_origin = pos.0 + shift + look.vec().as_dvec3();
+        commands.spawn((
+            EggEntityBundle {
+                thrown_item_item: Item(ItemStack::new(ItemKind::Egg, 1, None)),
+                entity_no_gravity: NoGravity(false),
+                position: Position(egg_origin),
+                velocity: Velocity(look.vec() * rules.speed),
+                look: *look,
+                layer: *entity_layer,
+                ..Default::default()
+            },
+            SpleggEgg,
+            Shooter(client),
+        ));
+    };
+    for event in item_interacts.read() {
+        process(event.client);
+    }
+    for event in block_interacts.read() {
+        process(event.client);
+    }
+}
+
+pub fn egg_movement(mut eggs: Query<(&mut Position, &mut Velocity), With<SpleggEgg>>) {
+    for (mut pos, mut vel) in eggs.iter_mut() {
+        // lighter than arrows, same as minecraft eggs
+        vel.0.y -= 0.6;
+        vel.0 *= 0.99;
+        let vel: DVec3 = vel.0.into();
+        pos.0 += vel / 20.0;
+    }
+}
+
+pub fn egg_oob(
+    eggs: Query<(Entity, &Position, &EntityLayerId), With<SpleggEgg>>,
+    arenas: Query<&ArenaBounds>,
+    mut commands: Commands,
+) {
+    for (e, pos, layer) in eggs.iter() {
+        let block_pos: BlockPos = pos.0.into();
+        let in_bounds = arenas
+            .get(layer.0)
+            .is_ok_and(|bounds| bounds.projectiles.contains(block_pos));
+        if !in_bounds {
+            commands.entity(e).insert(Despawned);
+        }
+    }
+}
+
+// Eggs fly through players and break the first block they hit
+pub fn egg_intersection(
+    eggs: Query<(Entity, &Position, &Velocity, &EntityLayerId, &Shooter), With<SpleggEgg>>,
+    arenas: Query<&ChunkLayer, With<ArenaLayer>>,
+    mut damage: EventWriter<BlockDamageEvent>,
+    mut commands: Commands,
+) {
+    for (e, pos, vel, layer, shooter) in eggs.iter() {
+        let Ok(arena) = arenas.get(layer.0) else {
+            continue;
+        };
+        let Some(ProjectileHit::Block(hit)) =
+            classes::projectile_hit(arena, pos, vel, std::iter::empty())
+        else {
+            continue;
+        };
+        damage.send(BlockDamageEvent {
+            layer: layer.0,
+            pos: hit.block,
+            amount: BlockDamageEvent::DESTROY,
+            source: shooter.0,
+            cause: DamageCause::Egg,
+        });
+        commands.entity(e).insert(Despawned);
+    }
+}
+
+fn near_edge(area: &Area, pos: DVec3, width: f64) -> bool {
+    let min = area.min();
+    let max = area.max();
+    let to_x = (pos.x - min.x as f64).min(max.x as f64 + 1.0 - pos.x);
+    let to_z = (pos.z - min.z as f64).min(max.z as f64 + 1.0 - pos.z);
+    to_x.min(to_z) <= width
+}
+
+pub fn refill_at_edge(
+    server: Res<Server>,
+    mut clients: Query<
+        (&Position, &EntityLayerId, &mut Inventory),
+        (With<ArenaPlayer>, With<EggLauncher>),
+    >,
+    arenas: Query<(&ArenaBounds, &Match), (With<ArenaLayer>, With<Splegg>)>,
+) {
+    for (pos, layer, mut inv) in clients.iter_mut() {
+        let Ok((bounds, game)) = arenas.get(layer.0) else {
+            continue;
+        };
+        let Some(rules) = splegg_rules(game).filter(|_| game.is_running()) else {
+            continue;
+        };
+        let (Some(max_ammo), Some(interval)) = (rules.ammo, rules.edge_refill_ticks()) else {
+            continue;
+        };
+        if server.current_tick() % interval != 0
+            || !near_edge(&bounds.area, pos.0, rules.edge_width)
+        {
+            continue;
+        }
+        let ammo = ammo_count(&inv);
+        if ammo < max_ammo {
+            set_ammo(&mut inv, ammo + 1);
+        }
+    }
+}
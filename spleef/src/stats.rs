@@ -0,0 +1,273 @@
+use crate::{
+    classes::{ClassRegistry, PlayerClass},
+    config::ConfigError,
+    damage::BlockDestroyedEvent,
+    game::{MatchEndedEvent, PlayerEliminatedEvent, PlayerLeftMatchEvent},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+use valence::{
+    app::AppExit, command::handler::CommandResultEvent, command_macros::Command, prelude::*,
+    DEFAULT_TPS,
+};
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct PlayerStats {
+    // Last known name, so /stats works for offline players
+    pub username: String,
+    pub matches_played: u64,
+    pub wins: u64,
+    pub eliminations: u64,
+    // Damage cause -> blocks
+    pub blocks_broken: BTreeMap<String, u64>,
+    // Class id -> ticks alive in running matches
+    pub ticks_survived: BTreeMap<String, u64>,
+}
+
+// Stats of every player who ever played, by uuid
+#[derive(Resource)]
+pub struct StatsStore {
+    path: PathBuf,
+    players: HashMap<String, PlayerStats>,
+    dirty: bool,
+    // Background write of the last save, so the game doesn't wait for the disk
+    writing: Option<JoinHandle<()>>,
+}
+
+impl StatsStore {
+    // Saved at least this often while something changes
+    const SAVE_INTERVAL_TICKS: i64 = DEFAULT_TPS.get() as i64 * 60;
+
+    // Starts empty if the file doesn't exist yet
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let players = match std::fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|e| {
+                ConfigError::Invalid(format!("can't parse {}: {e}", path.display()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(ConfigError::Io(path.to_owned(), e)),
+        };
+        Ok(Self {
+            path: path.to_owned(),
+            players,
+            dirty: false,
+            writing: None,
+        })
+    }
+
+    // Written to a temporary file first, so a crash doesn't leave half a file behind
+    fn write(path: &Path, data: &str) -> std::io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, path)
+    }
+
+    fn wait_for_write(&mut self) {
+        if let Some(writing) = self.writing.take() {
+            // the thread logs its own errors
+            let _ = writing.join();
+        }
+    }
+
+    // Waits until the file is written, for shutdown
+    pub fn save(&mut self) -> std::io::Result<()> {
+        self.wait_for_write();
+        let data = serde_json::to_string_pretty(&self.players)?;
+        Self::write(&self.path, &data)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn save_in_background(&mut self) {
+        self.wait_for_write();
+        let data = match serde_json::to_string_pretty(&self.players) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("can't serialize stats: {err}");
+                return;
+            }
+        };
+        let path = self.path.clone();
+        self.writing = Some(std::thread::spawn(move || {
+            if let Err(err) = Self::write(&path, &data) {
+                eprintln!("can't save stats to {}: {err}", path.display());
+            }
+        }));
+        self.dirty = false;
+    }
+
+    pub fn get(&self, uuid: &UniqueId) -> Option<&PlayerStats> {
+        self.players.get(&uuid.0.to_string())
+    }
+
+    pub fn find_by_name(&self, username: &str) -> Option<&PlayerStats> {
+        self.players
+            .values()
+            .find(|stats| stats.username.eq_ignore_ascii_case(username))
+    }
+
+    fn entry(&mut self, uuid: &UniqueId, username: &Username) -> &mut PlayerStats {
+        self.dirty = true;
+        let stats = self.players.entry(uuid.0.to_string()).or_default();
+        if stats.username != username.0 {
+            stats.username = username.0.clone();
+        }
+        stats
+    }
+}
+
+pub fn record_eliminations(
+    mut events: EventReader<PlayerEliminatedEvent>,
+    players: Query<(&UniqueId, &Username)>,
+    registry: Res<ClassRegistry>,
+    mut store: ResMut<StatsStore>,
+) {
+    for event in events.read() {
+        if let Ok((uuid, username)) = players.get(event.player) {
+            let class = registry.get(event.class).id.clone();
+            *store
+                .entry(uuid, username)
+                .ticks_survived
+                .entry(class)
+                .or_default() += event.survived_ticks.max(0) as u64;
+        }
+        let attacker = event.attacker.filter(|&attacker| attacker != event.player);
+        if let Some(Ok((uuid, username))) = attacker.map(|attacker| players.get(attacker)) {
+            store.entry(uuid, username).eliminations += 1;
+        }
+    }
+}
+
+pub fn record_matches(
+    mut events: EventReader<MatchEndedEvent>,
+    players: Query<(&UniqueId, &Username, Option<&PlayerClass>)>,
+    registry: Res<ClassRegistry>,
+    mut store: ResMut<StatsStore>,
+) {
+    let mut any = false;
+    for event in events.read() {
+        any = true;
+        for &participant in event.participants.iter() {
+            let Ok((uuid, username, class)) = players.get(participant) else {
+                continue;
+            };
+            let stats = store.entry(uuid, username);
+            stats.matches_played += 1;
+            if event.winners.contains(&participant) {
+                stats.wins += 1;
+            }
+            // eliminated players are counted in record_eliminations
+            if !event.survivors.contains(&participant) {
+                continue;
+            }
+            if let Some(class) = class {
+                let class = registry.get(class.0).id.clone();
+                *stats.ticks_survived.entry(class).or_default() += event.elapsed.max(0) as u64;
+            }
+        }
+    }
+    if any {
+        store.save_in_background();
+    }
+}
+
+// Leaving doesn't dodge the match, it's played and lost
+pub fn record_leaves(
+    mut events: EventReader<PlayerLeftMatchEvent>,
+    players: Query<(&UniqueId, &Username)>,
+    mut store: ResMut<StatsStore>,
+) {
+    for event in events.read() {
+        if let Ok((uuid, username)) = players.get(event.player) {
+            store.entry(uuid, username).matches_played += 1;
+        }
+    }
+}
+
+pub fn record_blocks(
+    mut events: EventReader<BlockDestroyedEvent>,
+    players: Query<(&UniqueId, &Username)>,
+    mut store: ResMut<StatsStore>,
+) {
+    for event in events.read() {
+        let Ok((uuid, username)) = players.get(event.source) else {
+            continue;
+        };
+        *store
+            .entry(uuid, username)
+            .blocks_broken
+            .entry(event.cause.name().to_owned())
+            .or_default() += 1;
+    }
+}
+
+pub fn save_stats(server: Res<Server>, mut store: ResMut<StatsStore>) {
+    if store.dirty && server.current_tick() % StatsStore::SAVE_INTERVAL_TICKS == 0 {
+        store.save_in_background();
+    }
+}
+
+pub fn save_stats_on_exit(mut exit: EventReader<AppExit>, mut store: ResMut<StatsStore>) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    if let Err(err) = store.save() {
+        eprintln!("can't save stats to {}: {err}", store.path.display());
+    }
+}
+
+#[derive(Command, Debug, Clone)]
+#[paths("stats {player?}")]
+#[scopes("spleef.command.stats")]
+pub struct StatsCommand {
+    player: Option<String>,
+}
+
+fn format_ticks(ticks: u64) -> String {
+    let seconds = ticks / DEFAULT_TPS.get() as u64;
+    format!("{}m {}s", seconds / 60, seconds % 60)
+}
+
+fn stats_message(stats: &PlayerStats) -> Text {
+    let mut lines = vec![
+        format!("Matches played: {}", stats.matches_played),
+        format!("Wins: {}", stats.wins),
+        format!("Eliminations: {}", stats.eliminations),
+    ];
+    for (cause, count) in stats.blocks_broken.iter() {
+        lines.push(format!("Blocks broken by {cause}: {count}"));
+    }
+    for (class, ticks) in stats.ticks_survived.iter() {
+        lines.push(format!("Survived as {class}: {}", format_ticks(*ticks)));
+    }
+    lines.into_iter().fold(
+        format!("Stats of {}", stats.username).bold(),
+        |text, line| text + "\n" + line,
+    )
+}
+
+pub fn handle_stats_command(
+    mut events: EventReader<CommandResultEvent<StatsCommand>>,
+    mut clients: Query<(&mut Client, &UniqueId)>,
+    store: Res<StatsStore>,
+) {
+    for event in events.read() {
+        let Ok((mut client, uuid)) = clients.get_mut(event.executor) else {
+            continue;
+        };
+        let stats = match &event.result.player {
+            Some(name) => store.find_by_name(name),
+            None => store.get(uuid),
+        };
+        match stats {
+            Some(stats) => client.send_chat_message(stats_message(stats)),
+            None => client.send_chat_message("No stats yet".color(Color::RED)),
+        }
+    }
+}
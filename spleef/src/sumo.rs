@@ -0,0 +1,223 @@
+use crate::{
+    classes::{ClassId, ClassRegistry},
+    config::SumoRules,
+    game::{Match, PlayerEliminatedEvent, Spectator, Watcher},
+    hud::TitleMessage,
+    level::{arena_player_bundle, ArenaLayer, ArenaPlayer},
+    map::SpawnPoints,
+};
+use std::collections::HashMap;
+use valence::prelude::*;
+
+// Sumo state of an arena, the match is played in rounds until somebody wins enough of them
+#[derive(Component, Default)]
+pub struct Sumo {
+    round: u32,
+    wins: HashMap<Entity, u32>,
+    // Class of every knocked out player, to bring them back next round
+    classes: HashMap<Entity, ClassId>,
+    // Ticks until the next round starts
+    intermission: Option<i64>,
+}
+
+// What update_match should do with a running sumo match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SumoRound {
+    // Round is being played, or the next one is about to start
+    Playing,
+    Won { round: u32, wins: u32 },
+    // Everyone fell off in the same tick, the round is replayed
+    Drawn { round: u32 },
+    // Somebody won enough rounds, the match ends as usual
+    Decided,
+}
+
+impl Sumo {
+    // Called every running tick with the sides still on the platform
+    // and one of their players
+    pub fn check_round(
+        &mut self,
+        sides: usize,
+        winner: Option<Entity>,
+        rules: &SumoRules,
+    ) -> SumoRound {
+        if self.intermission.is_some() || sides > 1 {
+            return SumoRound::Playing;
+        }
+        let Some(winner) = winner else {
+            self.intermission = Some(rules.intermission_ticks());
+            return SumoRound::Drawn { round: self.round };
+        };
+        let wins = self.wins.entry(winner).or_default();
+        *wins += 1;
+        if *wins >= rules.wins_needed() {
+            return SumoRound::Decided;
+        }
+        let wins = *wins;
+        self.intermission = Some(rules.intermission_ticks());
+        SumoRound::Won {
+            round: self.round,
+            wins,
+        }
+    }
+
+    // Counts down the pause between rounds, true when the next round starts
+    fn tick(&mut self) -> bool {
+        if self.round == 0 {
+            self.round = 1;
+        }
+        match self.intermission {
+            Some(timer) if timer > 0 => {
+                self.intermission = Some(timer - 1);
+                false
+            }
+            Some(_) => {
+                self.intermission = None;
+                self.round += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Runs before update_match with apply_deferred in between,
+// so revived players are counted on the tick their round starts
+pub fn update_sumo_rounds(
+    mut arenas: Query<(Entity, &Match, &mut Sumo, &mut SpawnPoints), With<ArenaLayer>>,
+    mut players: Query<
+        (Entity, &EntityLayerId, &mut Position, Has<Spectator>),
+        (Or<(With<ArenaPlayer>, With<Spectator>)>, Without<Watcher>),
+    >,
+    mut eliminated: EventReader<PlayerEliminatedEvent>,
+    registry: Res<ClassRegistry>,
+    mut commands: Commands,
+) {
+    for event in eliminated.read() {
+        if let Ok((_, _, mut sumo, _)) = arenas.get_mut(event.arena) {
+            sumo.classes.insert(event.player, event.class);
+        }
+    }
+
+    for (arena, game, mut sumo, mut spawns) in arenas.iter_mut() {
+        if !game.is_running() {
+            if sumo.round != 0 {
+                *sumo = Sumo::default();
+            }
+            continue;
+        }
+        if !sumo.tick() {
+            continue;
+        }
+        let title = TitleMessage::new(format!("Round {}", sumo.round).bold(), "Push them off");
+        for (e, _, mut pos, knocked_out) in players
+            .iter_mut()
+            .filter(|(_, layer, _, _)| layer.0 == arena)
+        {
+            if knocked_out {
+                let Some(&class) = sumo.classes.get(&e) else {
+                    continue;
+                };
+                // move_to_arena puts them on a spawn point
+                commands
+                    .entity(e)
+                    .remove::<Spectator>()
+                    .insert(arena_player_bundle(arena, class, &registry));
+            } else {
+                pos.set(spawns.next());
+            }
+            commands.entity(e).insert(title.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> SumoRules {
+        SumoRules {
+            best_of: 3,
+            intermission_seconds: 1,
+        }
+    }
+
+    // Ticks through the intermission, the standing player can't win it
+    fn wait_for_next_round(sumo: &mut Sumo, standing: Entity, rules: &SumoRules) {
+        for _ in 0..rules.intermission_ticks() {
+            assert!(!sumo.tick());
+            assert_eq!(
+                sumo.check_round(1, Some(standing), rules),
+                SumoRound::Playing
+            );
+        }
+        assert!(sumo.tick());
+    }
+
+    #[test]
+    fn two_rounds_decide_best_of_three() {
+        let rules = rules();
+        let a = Entity::from_raw(1);
+        let mut sumo = Sumo::default();
+
+        assert!(!sumo.tick());
+        assert_eq!(sumo.round, 1);
+        assert_eq!(sumo.check_round(2, Some(a), &rules), SumoRound::Playing);
+        assert_eq!(
+            sumo.check_round(1, Some(a), &rules),
+            SumoRound::Won { round: 1, wins: 1 }
+        );
+
+        wait_for_next_round(&mut sumo, a, &rules);
+        assert_eq!(sumo.round, 2);
+        // the loser is back on the platform before the round is checked
+        assert_eq!(sumo.check_round(2, Some(a), &rules), SumoRound::Playing);
+        assert_eq!(sumo.check_round(1, Some(a), &rules), SumoRound::Decided);
+    }
+
+    #[test]
+    fn split_rounds_need_a_third() {
+        let rules = rules();
+        let a = Entity::from_raw(1);
+        let b = Entity::from_raw(2);
+        let mut sumo = Sumo::default();
+
+        sumo.tick();
+        assert_eq!(
+            sumo.check_round(1, Some(a), &rules),
+            SumoRound::Won { round: 1, wins: 1 }
+        );
+        wait_for_next_round(&mut sumo, a, &rules);
+        assert_eq!(
+            sumo.check_round(1, Some(b), &rules),
+            SumoRound::Won { round: 2, wins: 1 }
+        );
+        wait_for_next_round(&mut sumo, b, &rules);
+        assert_eq!(sumo.round, 3);
+        assert_eq!(sumo.check_round(1, Some(b), &rules), SumoRound::Decided);
+    }
+
+    #[test]
+    fn empty_round_is_a_draw() {
+        let rules = rules();
+        let a = Entity::from_raw(1);
+        let mut sumo = Sumo::default();
+
+        sumo.tick();
+        assert_eq!(
+            sumo.check_round(0, None, &rules),
+            SumoRound::Drawn { round: 1 }
+        );
+        assert!(sumo.wins.is_empty());
+        for _ in 0..rules.intermission_ticks() {
+            assert!(!sumo.tick());
+            assert_eq!(sumo.check_round(0, None, &rules), SumoRound::Playing);
+        }
+        assert!(sumo.tick());
+        assert_eq!(sumo.round, 2);
+        assert_eq!(
+            sumo.check_round(1, Some(a), &rules),
+            SumoRound::Won { round: 2, wins: 1 }
+        );
+    }
+}
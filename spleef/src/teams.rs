@@ -0,0 +1,228 @@
+use crate::{
+    classes::{ClassId, PlayerClass},
+    game::Match,
+    level::{ArenaLayer, ArenaPlayer},
+};
+use std::collections::HashMap;
+use valence::{prelude::*, protocol::packets::play::team_s2c::TeamColor};
+
+pub struct TeamDef {
+    pub name: &'static str,
+    // Blocks of this colour can't be broken by the team
+    pub wool: BlockState,
+    pub color: Color,
+    // Name tag colour
+    pub tag_color: TeamColor,
+    // Tab list team, sorted next to the other arena players
+    pub tab_team: &'static str,
+}
+
+// Most distinct colours first, so small team counts look good
+pub const TEAMS: [TeamDef; 16] = [
+    team(
+        "Red",
+        "0_red",
+        BlockState::RED_WOOL,
+        Color::RED,
+        TeamColor::Red,
+    ),
+    team(
+        "Blue",
+        "0_blue",
+        BlockState::BLUE_WOOL,
+        Color::BLUE,
+        TeamColor::Blue,
+    ),
+    team(
+        "Lime",
+        "0_lime",
+        BlockState::LIME_WOOL,
+        Color::GREEN,
+        TeamColor::BrightGreen,
+    ),
+    team(
+        "Yellow",
+        "0_yellow",
+        BlockState::YELLOW_WOOL,
+        Color::YELLOW,
+        TeamColor::Yellow,
+    ),
+    team(
+        "Orange",
+        "0_orange",
+        BlockState::ORANGE_WOOL,
+        Color::GOLD,
+        TeamColor::Gold,
+    ),
+    team(
+        "Cyan",
+        "0_cyan",
+        BlockState::CYAN_WOOL,
+        Color::DARK_AQUA,
+        TeamColor::DarkCyan,
+    ),
+    team(
+        "Purple",
+        "0_purple",
+        BlockState::PURPLE_WOOL,
+        Color::DARK_PURPLE,
+        TeamColor::Purple,
+    ),
+    team(
+        "Pink",
+        "0_pink",
+        BlockState::PINK_WOOL,
+        Color::LIGHT_PURPLE,
+        TeamColor::Pink,
+    ),
+    team(
+        "White",
+        "0_white",
+        BlockState::WHITE_WOOL,
+        Color::WHITE,
+        TeamColor::White,
+    ),
+    team(
+        "Black",
+        "0_black",
+        BlockState::BLACK_WOOL,
+        Color::BLACK,
+        TeamColor::Black,
+    ),
+    team(
+        "Light Blue",
+        "0_light_blue",
+        BlockState::LIGHT_BLUE_WOOL,
+        Color::AQUA,
+        TeamColor::Cyan,
+    ),
+    team(
+        "Magenta",
+        "0_magenta",
+        BlockState::MAGENTA_WOOL,
+        Color::LIGHT_PURPLE,
+        TeamColor::Pink,
+    ),
+    team(
+        "Green",
+        "0_green",
+        BlockState::GREEN_WOOL,
+        Color::DARK_GREEN,
+        TeamColor::DarkGreen,
+    ),
+    team(
+        "Brown",
+        "0_brown",
+        BlockState::BROWN_WOOL,
+        Color::DARK_RED,
+        TeamColor::DarkRed,
+    ),
+    team(
+        "Gray",
+        "0_gray",
+        BlockState::GRAY_WOOL,
+        Color::DARK_GRAY,
+        TeamColor::DarkGray,
+    ),
+    team(
+        "Light Gray",
+        "0_light_gray",
+        BlockState::LIGHT_GRAY_WOOL,
+        Color::GRAY,
+        TeamColor::Gray,
+    ),
+];
+
+const fn team(
+    name: &'static str,
+    tab_team: &'static str,
+    wool: BlockState,
+    color: Color,
+    tag_color: TeamColor,
+) -> TeamDef {
+    TeamDef {
+        name,
+        wool,
+        color,
+        tag_color,
+        tab_team,
+    }
+}
+
+// Index in TEAMS, only arena players in team modes have it
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Team(pub u8);
+
+impl Team {
+    pub fn all() -> impl Iterator<Item = Team> {
+        (0..TEAMS.len() as u8).map(Team)
+    }
+
+    pub fn def(self) -> &'static TeamDef {
+        &TEAMS[self.0 as usize]
+    }
+
+    pub fn text(self) -> Text {
+        let def = self.def();
+        format!("{} team", def.name).color(def.color)
+    }
+}
+
+// Name and colour of a wool block, for messages
+pub fn wool_def(wool: BlockState) -> Option<&'static TeamDef> {
+    TEAMS.iter().find(|def| def.wool == wool)
+}
+
+// Players of the same team can't hurt each other
+pub fn allies(a: Option<&Team>, b: Option<&Team>) -> bool {
+    a.is_some() && a == b
+}
+
+#[derive(Default)]
+struct TeamCount {
+    total: usize,
+    classes: HashMap<ClassId, usize>,
+}
+
+// New arena players join the team with the fewest players of their class
+pub fn assign_teams(
+    mut unassigned: Query<
+        (Entity, &mut Client, &EntityLayerId, &PlayerClass),
+        (With<ArenaPlayer>, Without<Team>),
+    >,
+    assigned: Query<(&EntityLayerId, &PlayerClass, &Team), With<ArenaPlayer>>,
+    arenas: Query<&Match, With<ArenaLayer>>,
+    mut commands: Commands,
+) {
+    let mut counts: HashMap<Entity, Vec<TeamCount>> = HashMap::new();
+    for (e, mut client, layer, class) in unassigned.iter_mut() {
+        // still moving from the lobby, or not a team mode
+        let Some(team_count) = arenas.get(layer.0).ok().and_then(|game| game.rules.teams) else {
+            continue;
+        };
+        let teams = counts.entry(layer.0).or_insert_with(|| {
+            let mut teams: Vec<TeamCount> = (0..team_count).map(|_| TeamCount::default()).collect();
+            for (_, class, team) in assigned.iter().filter(|(l, _, _)| l.0 == layer.0) {
+                if let Some(count) = teams.get_mut(team.0 as usize) {
+                    count.total += 1;
+                    *count.classes.entry(class.0).or_default() += 1;
+                }
+            }
+            teams
+        });
+        let Some((index, count)) = teams.iter_mut().enumerate().min_by_key(|(_, count)| {
+            (
+                count.classes.get(&class.0).copied().unwrap_or(0),
+                count.total,
+            )
+        }) else {
+            continue;
+        };
+        count.total += 1;
+        *count.classes.entry(class.0).or_default() += 1;
+
+        let team = Team(index as u8);
+        commands.entity(e).insert(team);
+        client.send_chat_message("You're in the ".into_text() + team.text().bold());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    damage::{BlockDamageEvent, DamageCause},
+    dynamic_blocks::DynamicBlocks,
+    game::Match,
+    hud::ActionBarMessage,
+    level::{self, ArenaLayer, ArenaPlayer},
+};
+use std::collections::HashMap;
+use valence::{entity::OnGround, prelude::*};
+
+// TNT Run state of an arena, replaces the usual standing damage
+#[derive(Component)]
+pub struct TntRun {
+    // Block somebody stepped on -> player who stepped on it first
+    marked: HashMap<BlockPos, Entity>,
+    // Elimination heights of the floors, top to bottom
+    floors: Vec<f64>,
+    // Floor index every player is on, to tell them when they fall
+    player_floors: HashMap<Entity, usize>,
+}
+
+impl TntRun {
+    pub fn new(floors: Vec<f64>) -> Self {
+        Self {
+            marked: HashMap::new(),
+            floors,
+            player_floors: HashMap::new(),
+        }
+    }
+
+    fn floor_of(&self, y: f64) -> usize {
+        self.floors
+            .iter()
+            .take_while(|&&floor_y| y < floor_y)
+            .count()
+    }
+
+    pub fn forget(&mut self, player: Entity) {
+        self.player_floors.remove(&player);
+    }
+
+    // Nothing of the last match carries over to the next one
+    pub fn reset(&mut self) {
+        self.marked.clear();
+        self.player_floors.clear();
+    }
+}
+
+// Marked blocks lose 1 hp every tick, durability of the palette is the delay
+pub fn crumble_stepped_blocks(
+    mut arenas: Query<(Entity, &mut TntRun, &DynamicBlocks, &Match), With<ArenaLayer>>,
+    players: Query<(Entity, &Position, &OnGround, &EntityLayerId), With<ArenaPlayer>>,
+    mut damage: EventWriter<BlockDamageEvent>,
+) {
+    for (player, pos, ground, layer) in players.iter() {
+        if !ground.0 {
+            continue;
+        }
+        let Ok((_, mut tnt, dynamic, game)) = arenas.get_mut(layer.0) else {
+            continue;
+        };
+        if !game.is_running() {
+            continue;
+        }
+        if let Some(block) = level::block_under_player(pos.0, dynamic) {
+            tnt.marked.entry(block).or_insert(player);
+        }
+    }
+
+    for (arena, mut tnt, dynamic, game) in arenas.iter_mut() {
+        if !game.is_running() {
+            tnt.marked.clear();
+            continue;
+        }
+        tnt.marked.retain(|&pos, _| dynamic.contains(pos));
+        for (&pos, &source) in tnt.marked.iter() {
+            damage.send(BlockDamageEvent {
+                layer: arena,
+                pos,
+                amount: 1,
+                source,
+                cause: DamageCause::Standing,
+            });
+        }
+    }
+}
+
+pub fn announce_floors(
+    mut arenas: Query<(Entity, &mut TntRun, &Match), With<ArenaLayer>>,
+    players: Query<(Entity, &Position, &EntityLayerId), With<ArenaPlayer>>,
+    mut commands: Commands,
+) {
+    for (arena, mut tnt, game) in arenas.iter_mut() {
+        if !game.is_running() || tnt.floors.len() < 2 {
+            tnt.player_floors.clear();
+            continue;
+        }
+        let tnt = tnt.as_mut();
+        // eliminated players and the ones who left
+        tnt.player_floors.retain(|&player, _| {
+            players
+                .get(player)
+                .is_ok_and(|(_, _, layer)| layer.0 == arena)
+        });
+        for (player, pos, _) in players.iter().filter(|(_, _, layer)| layer.0 == arena) {
+            let floor = tnt.floor_of(pos.0.y);
+            // below the last floor is elimination
+            if floor >= tnt.floors.len() {
+                continue;
+            }
+            let previous = tnt.player_floors.insert(player, floor);
+            if previous.is_some_and(|previous| previous < floor) {
+                commands.entity(player).insert(ActionBarMessage(
+                    format!("Floor {} of {}", floor + 1, tnt.floors.len()).into_text(),
+                ));
+            }
+        }
+    }
+}